@@ -0,0 +1,186 @@
+use std::f32::consts::PI;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use rand::random;
+use crate::{Color, Vec3};
+use crate::distribution::Distribution2D;
+use crate::image::Image;
+use crate::sky::PhysicalSky;
+
+/// Equirectangular image surrounding the scene, with the center of the image towards -z
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+    image: Arc<Image>,
+    /// Around the y axis, in radians
+    rotation: f32,
+    intensity: f32,
+    /// Over the pixels of the image, proportional to the light they send onto a sphere
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Arc<Image>) -> EnvironmentMap {
+        let mut func = Vec::with_capacity(image.width * image.height);
+        for y in 0..image.height {
+            // Rows near the poles cover less of the sphere
+            let sin_theta = (PI * (y as f32 + 0.5) / image.height as f32).sin();
+            func.extend((0..image.width).map(|x| image.pixel(x, y).luminance().max(0.0) * sin_theta));
+        }
+        let distribution = Distribution2D::new(&func, image.width, image.height);
+        EnvironmentMap { image, rotation: 0.0, intensity: 1.0, distribution }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<EnvironmentMap> {
+        Ok(EnvironmentMap::new(Arc::new(Image::load_hdr(path)?)))
+    }
+
+    /// Turns the map counterclockwise around the y axis, seen from above
+    pub fn with_rotation(mut self, degrees: f32) -> EnvironmentMap {
+        self.rotation = degrees.to_radians();
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> EnvironmentMap {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Image coordinates of a direction, `v` goes from the bottom of the image to the top
+    fn uv(&self, direction: &Vec3) -> (f32, f32) {
+        let d = direction.unit_vector();
+        let (sin, cos) = self.rotation.sin_cos();
+        // Undo the rotation of the map
+        let (x, z) = (cos * d[0] - sin * d[2], sin * d[0] + cos * d[2]);
+        let u = 0.5 + x.atan2(-z) / (2.0 * PI);
+        let v = 0.5 + d[1].clamp(-1.0, 1.0).asin() / PI;
+        (u, v)
+    }
+
+    /// Direction with the given image coordinates, the inverse of `uv`
+    fn direction(&self, u: f32, v: f32) -> Vec3 {
+        let theta = PI * (1.0 - v);
+        let phi = 2.0 * PI * (u - 0.5);
+        let (x, y, z) = (theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos());
+        let (sin, cos) = self.rotation.sin_cos();
+        Vec3::new(cos * x + sin * z, y, cos * z - sin * x)
+    }
+
+    /// Random direction, mostly towards the bright parts of the map, and its solid angle density
+    pub fn sample_direction(&self) -> (Vec3, f32) {
+        let ((u, row), pdf) = self.distribution.sample(random::<f32>(), random::<f32>());
+        let direction = self.direction(u, 1.0 - row);
+        let sin_theta = (PI * row).sin();
+        if sin_theta <= 0.0 {
+            return (direction, 0.0);
+        }
+        (direction, pdf / (2.0 * PI * PI * sin_theta))
+    }
+
+    /// Solid angle density of `sample_direction` producing `direction`
+    pub fn pdf_value(&self, direction: &Vec3) -> f32 {
+        let (u, v) = self.uv(direction);
+        let d = direction.unit_vector();
+        let sin_theta = (1.0 - d[1] * d[1]).max(0.0).sqrt();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, 1.0 - v) / (2.0 * PI * PI * sin_theta)
+    }
+
+    pub fn color(&self, direction: &Vec3) -> Color {
+        let (u, v) = self.uv(direction);
+        // Rows must not wrap around over the poles
+        let half_row = 0.5 / self.image.height as f32;
+        self.image.sample(u, v.clamp(half_row, 1.0 - half_row)) * self.intensity
+    }
+}
+
+/// Radiance of rays leaving the scene, which is seen behind the objects and lights them
+#[derive(Debug, Clone)]
+pub enum Background {
+    Constant(Color),
+    /// Blends vertically from straight down to straight up
+    Gradient { bottom: Color, top: Color },
+    Environment(EnvironmentMap),
+    Sky(PhysicalSky),
+}
+
+impl Default for Background {
+    /// White to light blue sky
+    fn default() -> Background {
+        Background::Gradient { bottom: Color::new(1.0, 1.0, 1.0), top: Color::new(0.5, 0.7, 1.0) }
+    }
+}
+
+impl Background {
+    pub fn color(&self, direction: &Vec3) -> Color {
+        match self {
+            Background::Constant(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let t = 0.5 * (direction.unit_vector()[1] + 1.0);
+                *bottom * (1.0 - t) + *top * t
+            }
+            Background::Environment(map) => map.color(direction),
+            Background::Sky(sky) => sky.color(direction),
+        }
+    }
+
+    /// Only environment maps and the sky are sampled for direct lighting, the others are smooth
+    /// enough to be found by scattering
+    pub fn is_sampled(&self) -> bool {
+        matches!(self, Background::Environment(_) | Background::Sky(_))
+    }
+
+    /// Random direction towards the background, which must be sampled
+    pub fn sample_direction(&self) -> Vec3 {
+        match self {
+            Background::Environment(map) => map.sample_direction().0,
+            Background::Sky(sky) => sky.sample_direction(),
+            _ => Vec3::zero(),
+        }
+    }
+
+    /// Solid angle density of `sample_direction` producing `direction`, zero if not sampled
+    pub fn pdf_value(&self, direction: &Vec3) -> f32 {
+        match self {
+            Background::Environment(map) => map.pdf_value(direction),
+            Background::Sky(sky) => sky.pdf_value(direction),
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::{Color, Vec3};
+    use crate::background::EnvironmentMap;
+    use crate::image::Image;
+
+    #[test]
+    fn environment_map_follows_rotation() {
+        // Column edges face +z, -x, -z and +x, so those directions blend two columns
+        let pixels = (0..4).map(|i| Color::new(i as f32, 0.0, 0.0)).collect();
+        let map = EnvironmentMap::new(Arc::new(Image::new(4, 1, pixels))).with_intensity(2.0);
+        assert!((map.color(&Vec3::new(0.0, 0.0, -1.0))[0] - 3.0).abs() < 1e-5);
+        assert!((map.color(&Vec3::new(-1.0, 0.0, 0.0))[0] - 1.0).abs() < 1e-5);
+
+        let rotated = map.with_rotation(90.0);
+        assert!((rotated.color(&Vec3::new(-1.0, 0.0, 0.0))[0] - 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sampling_favours_bright_pixels() {
+        let mut pixels = vec![Color::new(0.1, 0.1, 0.1); 32 * 16];
+        pixels[5 * 32 + 20] = Color::new(1000.0, 1000.0, 1000.0);
+        let map = EnvironmentMap::new(Arc::new(Image::new(32, 16, pixels))).with_rotation(30.0);
+
+        let samples: Vec<_> = (0..1000).map(|_| map.sample_direction()).collect();
+        let bright = samples.iter().filter(|(direction, _)| map.color(direction)[0] > 100.0).count();
+        assert!(bright > 900);
+        // Round trips may land in the neighbouring pixel right at the edges
+        let consistent = samples.iter().filter(|(direction, pdf)| (map.pdf_value(direction) - pdf).abs() <= 1e-3 * pdf).count();
+        assert!(consistent > 990);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{Point3, Vec3};
-use crate::ray::Ray;
+use crate::ray::{Ray, RayDifferential};
 
 #[derive(Debug, Clone)]
 pub struct Camera {
@@ -7,7 +7,6 @@ pub struct Camera {
     lower_left_corner: Point3,
     horizontal: Vec3,
     vertical: Vec3,
-    w: Vec3,
     u: Vec3,
     v: Vec3,
     lens_radius: f32,
@@ -39,15 +38,31 @@ impl Camera {
         let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - w * focus_dist;
 
         let lens_radius = aperture / 2.;
-        Camera { origin, lower_left_corner, horizontal, vertical, w, u, v, lens_radius }
+        Camera { origin, lower_left_corner, horizontal, vertical, u, v, lens_radius }
     }
 
     pub fn get_ray(&self, u: f32, v: f32) -> Ray {
+        self.get_ray_differential(u, v, 0.0, 0.0)
+    }
+
+    /// Ray with differentials towards the points `du` and `dv` away on the viewport,
+    /// which should be the spacing of the pixels
+    pub fn get_ray_differential(&self, u: f32, v: f32, du: f32, dv: f32) -> Ray {
         let rd = Vec3::rand_unit() * self.lens_radius;
         let offset = self.u * rd[0] + self.v * rd[1];
 
+        let origin = self.origin + offset;
         let direction = self.lower_left_corner + self.horizontal * u + self.vertical * v - self.origin - offset;
-        Ray::new(self.origin + offset, direction)
+        let mut ray = Ray::new(origin, direction);
+        if du > 0.0 || dv > 0.0 {
+            ray.differential = Some(RayDifferential {
+                rx_origin: origin,
+                rx_direction: direction + self.horizontal * du,
+                ry_origin: origin,
+                ry_direction: direction + self.vertical * dv,
+            });
+        }
+        ray
     }
 }
 
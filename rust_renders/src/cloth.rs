@@ -0,0 +1,158 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+use rand::random;
+use crate::{Color, Vec3};
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
+
+/// Textile material: diffuse base with a Charlie sheen lobe (Estevez and Kulla 2017)
+/// and the visibility term of Neubelt and Pettineo 2013
+#[derive(Debug, Clone)]
+pub struct Cloth {
+    albedo: Arc<dyn Texture>,
+    sheen_color: Arc<dyn Texture>,
+    sheen_roughness: f32,
+}
+
+impl Cloth {
+    pub fn new(albedo: Color, sheen_color: Color, sheen_roughness: f32) -> Cloth {
+        Cloth::textured(Arc::new(SolidColor::new(albedo)), Arc::new(SolidColor::new(sheen_color)), sheen_roughness)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, sheen_color: Arc<dyn Texture>, sheen_roughness: f32) -> Cloth {
+        Cloth { albedo, sheen_color, sheen_roughness }
+    }
+
+    fn charlie_d(&self, cos_h: f32) -> f32 {
+        let inv_alpha = 1.0 / (self.sheen_roughness * self.sheen_roughness).max(1e-3);
+        let sin_h = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+        (2.0 + inv_alpha) * sin_h.powf(inv_alpha) / (2.0 * PI)
+    }
+
+    /// Albedo and sheen color at the hit
+    fn colors(&self, hit_rec: &HitRecord) -> (Color, Color) {
+        (self.albedo.value(hit_rec), self.sheen_color.value(hit_rec))
+    }
+
+    /// Probability of sampling the sheen lobe, the rest goes to the diffuse base
+    fn sheen_probability((albedo, sheen_color): (Color, Color)) -> f32 {
+        let sheen = sheen_color.luminance();
+        let total = sheen + albedo.luminance();
+        if total > 0.0 { sheen / total } else { 0.0 }
+    }
+
+    fn brdf(&self, (albedo, sheen_color): (Color, Color), n: &Vec3, wo: &Vec3, wi: &Vec3) -> Color {
+        let cos_o = wo.dot(*n).max(1e-4);
+        let cos_i = wi.dot(*n).max(1e-4);
+        let cos_h = (*wo + *wi).unit_vector().dot(*n);
+        let visibility = 1.0 / (4.0 * (cos_i + cos_o - cos_i * cos_o));
+        albedo * (1.0 / PI) + sheen_color * (self.charlie_d(cos_h) * visibility)
+    }
+
+    /// Uniform hemisphere for the sheen lobe mixed with cosine-weighted for the base
+    fn sampling_pdf(colors: (Color, Color), n: &Vec3, wi: &Vec3) -> f32 {
+        let p = Cloth::sheen_probability(colors);
+        p / (2.0 * PI) + (1.0 - p) * wi.dot(*n).max(0.0) / PI
+    }
+}
+
+impl Material for Cloth {
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        let n = hit_rec.normal;
+        let wo = -r_in.direction.unit_vector();
+        let uvw = Onb::from_w(&n);
+        let colors = self.colors(hit_rec);
+
+        let local = if random::<f32>() < Cloth::sheen_probability(colors) {
+            let z = random::<f32>();
+            let r = (1.0 - z * z).sqrt();
+            let phi = 2.0 * PI * random::<f32>();
+            Vec3::new(r * phi.cos(), r * phi.sin(), z)
+        } else {
+            Vec3::rand_cosine_direction()
+        };
+        let direction = uvw.local(&local);
+        let cos_i = direction.dot(n);
+        if cos_i <= 0.0 {
+            return None;
+        }
+
+        let pdf = Cloth::sampling_pdf(colors, &n, &direction);
+        let weight = self.brdf(colors, &n, &wo, &direction) * (cos_i / pdf);
+        Some(ScatterRecord::new(r_in.spawn(hit_rec.p, direction), weight, pdf))
+    }
+
+    fn eval(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> Color {
+        let n = hit_rec.normal;
+        let cos_i = direction.dot(n);
+        if cos_i <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.brdf(self.colors(hit_rec), &n, &-r_in.direction.unit_vector(), direction) * cos_i
+    }
+
+    fn pdf(&self, _r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> f32 {
+        if direction.dot(hit_rec.normal) <= 0.0 {
+            return 0.0;
+        }
+        Cloth::sampling_pdf(self.colors(hit_rec), &hit_rec.normal, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::{Color, Point3, Vec3};
+    use crate::cloth::Cloth;
+    use crate::furnace::{albedo, hit, integrate};
+    use crate::material::Material;
+    use crate::texture::{Checker, SolidColor, Texture};
+
+    #[test]
+    fn sheen_is_reciprocal() {
+        let cloth = Cloth::new(Color::new(0.5, 0.5, 0.5), Color::new(1.0, 1.0, 1.0), 0.5);
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let a = Vec3::new(0.9, 0.2, 0.1).unit_vector();
+        let b = Vec3::new(-0.4, 0.8, 0.3).unit_vector();
+        let colors = (Color::new(0.5, 0.5, 0.5), Color::new(1.0, 1.0, 1.0));
+        let ab = cloth.brdf(colors, &n, &a, &b).luminance();
+        let ba = cloth.brdf(colors, &n, &b, &a).luminance();
+        assert!(ab > 0.0);
+        assert!((ab - ba).abs() < 1e-5 * ab);
+    }
+
+    #[test]
+    fn sheen_grows_towards_grazing_angles() {
+        let sheen = Arc::new(Cloth::new(Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0), 0.5));
+        let normal = albedo(sheen.clone(), Vec3::new(0.0, 1.0, 0.0));
+        let grazing = albedo(sheen, Vec3::new(1.0, 0.1, 0.0).unit_vector());
+        assert!(normal > 0.0);
+        assert!(grazing > 5.0 * normal && grazing <= 1.0, "{} {}", normal, grazing);
+    }
+
+    #[test]
+    fn sampling_agrees_with_the_pdf() {
+        let cloth = Cloth::new(Color::new(0.5, 0.5, 0.5), Color::new(1.0, 1.0, 1.0), 0.5);
+        for &wo in &[Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.9, 0.2, 0.1).unit_vector()] {
+            let sampled = albedo(Arc::new(cloth.clone()), wo);
+            let integrated = integrate(Arc::new(cloth.clone()), wo).0.luminance();
+            assert!((sampled - integrated).abs() < 0.01, "{} {}", sampled, integrated);
+        }
+    }
+
+    #[test]
+    fn sheen_follows_its_texture() {
+        let white: Arc<dyn Texture> = Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0)));
+        let black: Arc<dyn Texture> = Arc::new(SolidColor::new(Color::new(0.0, 0.0, 0.0)));
+        let cloth: Arc<dyn Material> = Arc::new(Cloth::textured(black.clone(), Arc::new(Checker::new(white, black, 1.0)), 0.5));
+        let (r_in, mut hit_rec) = hit(cloth.clone(), Vec3::new(1.0, 0.2, 0.0).unit_vector());
+        let wi = Vec3::new(0.0, 0.2, 1.0).unit_vector();
+        hit_rec.p = Point3::new(0.5, 0.5, 0.5);
+        assert_eq!(cloth.eval(&r_in, &hit_rec, &wi).luminance(), 0.0);
+        hit_rec.p = Point3::new(1.5, 0.5, 0.5);
+        assert!(cloth.eval(&r_in, &hit_rec, &wi).luminance() > 0.0);
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+use crate::{Color, Vec3};
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::texture::Texture;
+
+/// Material with an opacity mask, like leaves or a fence modelled as textured quads.
+/// Luminance of the mask is the opacity, rays pass through the cut out parts
+#[derive(Debug, Clone)]
+pub struct Cutout {
+    base: Arc<dyn Material + Send + Sync>,
+    opacity: Arc<dyn Texture>,
+}
+
+impl Cutout {
+    pub fn new(base: Arc<dyn Material + Send + Sync>, opacity: Arc<dyn Texture>) -> Cutout {
+        Cutout { base, opacity }
+    }
+}
+
+impl Material for Cutout {
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        self.base.sample(r_in, hit_rec)
+    }
+
+    fn opacity(&self, hit_rec: &HitRecord) -> f32 {
+        self.opacity.value(hit_rec).luminance().clamp(0.0, 1.0)
+    }
+
+    fn emitted(&self, r_in: &Ray, hit_rec: &HitRecord) -> Color {
+        self.base.emitted(r_in, hit_rec)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+
+    fn eval(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> Color {
+        self.base.eval(r_in, hit_rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> f32 {
+        self.base.pdf(r_in, hit_rec, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::{Color, HittableArray, Lambertian, Point3, Sphere, Vec3};
+    use crate::cutout::Cutout;
+    use crate::hittable::Hittable;
+    use crate::quad::Quad;
+    use crate::ray::Ray;
+    use crate::texture::{Checker, SolidColor};
+
+    #[test]
+    fn cut_out_hits_are_skipped() {
+        let base = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mask = |opacity: f32| Arc::new(SolidColor::new(Color::new(opacity, opacity, opacity)));
+        let fence = Cutout::new(base.clone(), Arc::new(Checker::new(mask(0.0), mask(1.0), 1.0)));
+        let mut world = HittableArray::new();
+        world.add(Arc::new(Quad::new(Point3::new(-2.0, -2.0, -1.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0),
+                                     Arc::new(fence))));
+        world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, -5.0), 3.0, base.clone())));
+
+        // Through a hole of the checker onto the sphere behind, and onto a solid cell
+        let hole = world.hit(&Ray::new(Point3::new(0.5, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY);
+        assert!(hole.unwrap().t > 1.5);
+        let solid = world.hit(&Ray::new(Point3::new(1.5, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY);
+        assert!((solid.unwrap().t - 1.0).abs() < 1e-5);
+
+        // Both sides of an invisible sphere are skipped, half opacity stops half of the rays
+        let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let ghost = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Arc::new(Cutout::new(base.clone(), mask(0.0))));
+        assert!(ghost.hit(&ray, 0.001, f32::INFINITY).is_none());
+        let veil = Quad::new(Point3::new(-1.0, -1.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0),
+                             Arc::new(Cutout::new(base, mask(0.5))));
+        let stopped = (0..10000).filter(|_| veil.hit(&ray, 0.001, f32::INFINITY).is_some()).count();
+        assert!((stopped as f32 / 10000.0 - 0.5).abs() < 0.03);
+    }
+}
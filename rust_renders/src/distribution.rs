@@ -0,0 +1,108 @@
+/// Piecewise constant density over [0, 1) with one bin per value of the function
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    func: Vec<f32>,
+    /// `func.len() + 1` entries from 0 to 1
+    cdf: Vec<f32>,
+    /// Average of `func`
+    integral: f32,
+}
+
+impl Distribution1D {
+    /// `func` must not be empty or negative. An all zero function is sampled uniformly
+    pub fn new(func: &[f32]) -> Distribution1D {
+        let n = func.len();
+        debug_assert!(n > 0);
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for (i, f) in func.iter().enumerate() {
+            cdf.push(cdf[i] + f / n as f32);
+        }
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+            *c = if integral > 0.0 { *c / integral } else { i as f32 / n as f32 };
+        }
+        Distribution1D { func: func.to_vec(), cdf, integral }
+    }
+
+    fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    /// Maps `u` in [0, 1) to a sample, returning its position in [0, 1), its density and its bin
+    pub fn sample(&self, u: f32) -> (f32, f32, usize) {
+        // Last bin starting at or before `u`, skipping empty bins of the same start
+        let index = self.cdf.partition_point(|&c| c <= u).clamp(1, self.len()) - 1;
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 { ((u - self.cdf[index]) / width).clamp(0.0, 1.0) } else { 0.0 };
+        let x = ((index as f32 + offset) / self.len() as f32).min(1.0 - f32::EPSILON);
+        (x, self.pdf(index), index)
+    }
+
+    /// Density of the samples in bin `index`
+    pub fn pdf(&self, index: usize) -> f32 {
+        if self.integral > 0.0 { self.func[index] / self.integral } else { 1.0 }
+    }
+
+    /// Bin containing position `x` in [0, 1]
+    pub fn bin(&self, x: f32) -> usize {
+        ((x * self.len() as f32) as usize).min(self.len() - 1)
+    }
+}
+
+/// Piecewise constant density over the unit square, given row by row. Samples pick a row
+/// first and then a column within it
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f32], width: usize, height: usize) -> Distribution2D {
+        debug_assert_eq!(func.len(), width * height);
+        let rows: Vec<Distribution1D> = func.chunks(width).map(Distribution1D::new).collect();
+        let marginal = Distribution1D::new(&rows.iter().map(|r| r.integral()).collect::<Vec<_>>());
+        Distribution2D { rows, marginal }
+    }
+
+    /// Position `(x, y)` in the unit square and its density, where `y` indexes the rows
+    pub fn sample(&self, u1: f32, u2: f32) -> ((f32, f32), f32) {
+        let (y, row_pdf, row) = self.marginal.sample(u1);
+        let (x, column_pdf, _) = self.rows[row].sample(u2);
+        ((x, y), row_pdf * column_pdf)
+    }
+
+    pub fn pdf(&self, x: f32, y: f32) -> f32 {
+        let row = self.marginal.bin(y);
+        self.marginal.pdf(row) * self.rows[row].pdf(self.rows[row].bin(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distribution::{Distribution1D, Distribution2D};
+
+    #[test]
+    fn samples_follow_the_function() {
+        let distribution = Distribution1D::new(&[1.0, 0.0, 3.0]);
+        let (x, pdf, index) = distribution.sample(0.5);
+        assert_eq!(index, 2);
+        assert!((x - (2.0 + 1.0 / 3.0) / 3.0).abs() < 1e-5);
+        assert!((pdf - 2.25).abs() < 1e-5);
+        assert_eq!(distribution.sample(0.25).2, 2);
+        assert_eq!(distribution.sample(0.2).2, 0);
+
+        let grid = Distribution2D::new(&[0.0, 1.0, 2.0, 1.0], 2, 2);
+        for &(u1, u2) in &[(0.1, 0.3), (0.6, 0.9), (0.9, 0.1)] {
+            let ((x, y), pdf) = grid.sample(u1, u2);
+            assert!((grid.pdf(x, y) - pdf).abs() < 1e-5);
+            assert!(pdf > 0.0);
+        }
+        assert!((grid.pdf(0.75, 0.25) - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,61 @@
+//! White furnace harness for the material tests
+use std::f32::consts::PI;
+use std::sync::Arc;
+use crate::{Color, Point3, Vec3};
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::ray::Ray;
+
+const SAMPLES: usize = 100000;
+
+/// Ray arriving from `wo` at the origin, where the surface faces +y
+pub fn hit(material: Arc<dyn Material>, wo: Vec3) -> (Ray, HitRecord) {
+    let r_in = Ray::new(Point3::zero() + wo, -wo);
+    let mut hit_rec = HitRecord::new(Point3::zero(), Vec3::new(0.0, 1.0, 0.0), 1.0, material);
+    hit_rec.set_face_normal(&r_in, &Vec3::new(0.0, 1.0, 0.0));
+    (r_in, hit_rec)
+}
+
+/// Average weight of the light from `wo` scattered above and below the surface
+pub fn furnace(material: Arc<dyn Material>, wo: Vec3) -> (Color, Color) {
+    let (r_in, hit_rec) = hit(material.clone(), wo);
+    let mut reflected = Color::new(0.0, 0.0, 0.0);
+    let mut transmitted = Color::new(0.0, 0.0, 0.0);
+    for _ in 0..SAMPLES {
+        if let Some(record) = material.sample(&r_in, &hit_rec) {
+            if record.ray.direction[1] > 0.0 {
+                reflected += record.weight;
+            } else {
+                transmitted += record.weight;
+            }
+        }
+    }
+    (reflected * (1.0 / SAMPLES as f32), transmitted * (1.0 / SAMPLES as f32))
+}
+
+/// Luminance of all the scattered light, the albedo of the material seen from `wo`
+pub fn albedo(material: Arc<dyn Material>, wo: Vec3) -> f32 {
+    let (reflected, transmitted) = furnace(material, wo);
+    (reflected + transmitted).luminance()
+}
+
+/// `eval` for light from `wo` integrated above and below the surface with the midpoint rule,
+/// what `furnace` scatters when sampling agrees with `pdf`
+pub fn integrate(material: Arc<dyn Material>, wo: Vec3) -> (Color, Color) {
+    let (r_in, hit_rec) = hit(material.clone(), wo);
+    let steps = 256;
+    let mut sums = [Color::new(0.0, 0.0, 0.0); 2];
+    for (side, sum) in [1.0, -1.0].iter().zip(sums.iter_mut()) {
+        for i in 0..steps {
+            // Steps in the polar angle resolve lobes peaking at the normal
+            let theta = 0.5 * PI * (i as f32 + 0.5) / steps as f32;
+            let (sin_i, cos_i) = (theta.sin(), side * theta.cos());
+            for j in 0..steps {
+                let phi = 2.0 * PI * (j as f32 + 0.5) / steps as f32;
+                *sum += material.eval(&r_in, &hit_rec, &Vec3::new(sin_i * phi.cos(), cos_i, sin_i * phi.sin())) * sin_i;
+            }
+        }
+    }
+    let scale = PI * PI / (steps * steps) as f32;
+    (sums[0] * scale, sums[1] * scale)
+}
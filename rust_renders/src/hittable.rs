@@ -1,15 +1,37 @@
 use std::sync::Arc;
-use crate::{Point3, Vec3};
+use rand::random;
+use crate::{Color, Point3, Vec3};
+use crate::background::Background;
+use crate::light::DeltaLight;
 use crate::material::Material;
-use crate::ray::Ray;
+use crate::ray::{Ray, RayDifferential};
 
 #[derive(Debug, Clone)]
 pub struct HitRecord {
     pub p: Point3,
+    /// Shading normal, facing against the ray
     pub normal: Vec3,
+    /// Normal of the actual surface, facing against the ray
+    pub geometric_normal: Vec3,
     pub t: f32,
     pub material: Arc<dyn Material>,
     pub front_face: bool,
+    /// Surface coordinates used for texturing
+    pub u: f32,
+    pub v: f32,
+    /// Partial derivatives of the hit point over surface coordinates
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    /// Partial derivatives of the shading normal over surface coordinates, zero for flat surfaces
+    pub dndu: Vec3,
+    pub dndv: Vec3,
+    /// Change of the hit point and surface coordinates to the neighbouring pixels, zero when unknown
+    pub dpdx: Vec3,
+    pub dpdy: Vec3,
+    pub dudx: f32,
+    pub dvdx: f32,
+    pub dudy: f32,
+    pub dvdy: f32,
 }
 
 pub trait Hittable {
@@ -18,6 +40,31 @@ pub trait Hittable {
            t_min: f32,
            t_max: f32)
            -> Option<HitRecord>;
+
+    /// Emissive objects are collected by `HittableArray` and sampled directly for lighting
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Random direction from `origin` towards the object, for sampling its light
+    fn sample_direction(&self, _origin: &Point3) -> Vec3 {
+        Vec3::zero()
+    }
+
+    /// Solid angle density of `sample_direction` producing `direction`, zero if it misses the object
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f32 {
+        0.0
+    }
+
+    /// Lights without geometry illuminating the object
+    fn delta_lights(&self) -> &[Arc<dyn DeltaLight + Send + Sync>] {
+        &[]
+    }
+
+    /// Radiance arriving along rays that leave the scene in `direction`
+    fn background(&self, direction: &Vec3) -> Color {
+        Background::default().color(direction)
+    }
 }
 
 impl HitRecord {
@@ -27,7 +74,26 @@ impl HitRecord {
                material: Arc<dyn Material>)
                -> HitRecord
     {
-        HitRecord { p, normal, t, material, front_face: false }
+        HitRecord {
+            p,
+            normal,
+            geometric_normal: normal,
+            t,
+            material,
+            front_face: false,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::zero(),
+            dpdv: Vec3::zero(),
+            dndu: Vec3::zero(),
+            dndv: Vec3::zero(),
+            dpdx: Vec3::zero(),
+            dpdy: Vec3::zero(),
+            dudx: 0.0,
+            dvdx: 0.0,
+            dudy: 0.0,
+            dvdy: 0.0,
+        }
     }
 
     pub fn set_face_normal(&mut self,
@@ -35,35 +101,162 @@ impl HitRecord {
                            outward_normal: &Vec3)
     {
         self.front_face = r.direction.dot(*outward_normal) < 0.0;
-        self.normal = if self.front_face { outward_normal.clone() } else { -*outward_normal };
+        self.normal = if self.front_face { *outward_normal } else { -*outward_normal };
+        self.geometric_normal = self.normal;
+    }
+
+    /// Whether the ray should pass through the hit point because the surface is masked out there.
+    /// Partial opacity lets a matching fraction of rays through
+    pub fn is_cut_out(&self) -> bool {
+        let opacity = self.material.opacity(self);
+        opacity < 1.0 && random::<f32>() >= opacity
+    }
+
+    /// Estimates the pixel footprint on the surface by intersecting the differential rays
+    /// with the tangent plane at the hit point (Igehy 1999)
+    pub fn compute_differentials(&mut self, ray: &Ray) {
+        let differential = match &ray.differential {
+            Some(differential) => differential,
+            None => return,
+        };
+        let n = self.geometric_normal;
+        let plane = n.dot(self.p);
+        let tx = (plane - n.dot(differential.rx_origin)) / n.dot(differential.rx_direction);
+        let ty = (plane - n.dot(differential.ry_origin)) / n.dot(differential.ry_direction);
+        if !tx.is_finite() || !ty.is_finite() {
+            return;
+        }
+        self.dpdx = differential.rx_origin + differential.rx_direction * tx - self.p;
+        self.dpdy = differential.ry_origin + differential.ry_direction * ty - self.p;
+
+        // Least squares solution for the surface coordinates, dropping the axis the normal is closest to
+        let (a0, a1) = if n[0].abs() > n[1].abs() && n[0].abs() > n[2].abs() {
+            (1, 2)
+        } else if n[1].abs() > n[2].abs() {
+            (0, 2)
+        } else {
+            (0, 1)
+        };
+        let (dpdu, dpdv) = (self.dpdu, self.dpdv);
+        let det = dpdu[a0] * dpdv[a1] - dpdv[a0] * dpdu[a1];
+        if det.abs() < 1e-12 {
+            return;
+        }
+        let solve = |delta: Vec3| ((dpdv[a1] * delta[a0] - dpdv[a0] * delta[a1]) / det,
+                                   (dpdu[a0] * delta[a1] - dpdu[a1] * delta[a0]) / det);
+        let (dudx, dvdx) = solve(self.dpdx);
+        let (dudy, dvdy) = solve(self.dpdy);
+        self.dudx = dudx;
+        self.dvdx = dvdx;
+        self.dudy = dudy;
+        self.dvdy = dvdy;
+    }
+
+    /// Change of the shading normal to the neighbouring pixels
+    fn normal_differentials(&self) -> (Vec3, Vec3) {
+        (self.dndu * self.dudx + self.dndv * self.dvdx, self.dndu * self.dudy + self.dndv * self.dvdy)
+    }
+
+    /// Differential of a ray mirrored into the unit direction `reflected`
+    pub fn reflected_differential(&self, r_in: &Ray, reflected: &Vec3) -> Option<RayDifferential> {
+        let differential = r_in.differential?;
+        let n = self.normal;
+        let wo = -r_in.direction.unit_vector();
+        let (dndx, dndy) = self.normal_differentials();
+        let direction = |rd: Vec3, dndx: Vec3| {
+            let dwodx = -rd.unit_vector() - wo;
+            let ddndx = dwodx.dot(n) + wo.dot(dndx);
+            *reflected - dwodx + (dndx * wo.dot(n) + n * ddndx) * 2.0
+        };
+        Some(RayDifferential {
+            rx_origin: self.p + self.dpdx,
+            rx_direction: direction(differential.rx_direction, dndx),
+            ry_origin: self.p + self.dpdy,
+            ry_direction: direction(differential.ry_direction, dndy),
+        })
+    }
+
+    /// Differential of a ray refracted into the unit direction `refracted` with the given ratio of refraction indices
+    pub fn refracted_differential(&self,
+                                  r_in: &Ray,
+                                  refracted: &Vec3,
+                                  etai_over_etat: f32)
+                                  -> Option<RayDifferential>
+    {
+        let differential = r_in.differential?;
+        let n = self.normal;
+        let eta = etai_over_etat;
+        let wo = -r_in.direction.unit_vector();
+        let cos_t = refracted.dot(n).abs().max(1e-4);
+        let mu = eta * wo.dot(n) - cos_t;
+        let (dndx, dndy) = self.normal_differentials();
+        let direction = |rd: Vec3, dndx: Vec3| {
+            let dwodx = -rd.unit_vector() - wo;
+            let ddndx = dwodx.dot(n) + wo.dot(dndx);
+            let dmudx = (eta - eta * eta * wo.dot(n) / cos_t) * ddndx;
+            *refracted - dwodx * eta + dndx * mu + n * dmudx
+        };
+        Some(RayDifferential {
+            rx_origin: self.p + self.dpdx,
+            rx_direction: direction(differential.rx_direction, dndx),
+            ry_origin: self.p + self.dpdy,
+            ry_direction: direction(differential.ry_direction, dndy),
+        })
+    }
+
+    /// Origin for a ray leaving the surface in `direction`, pushed off the actual surface to avoid self-intersection
+    pub fn offset_origin(&self, direction: &Vec3) -> Point3 {
+        const OFFSET: f32 = 1e-4;
+        if direction.dot(self.geometric_normal) > 0.0 {
+            self.p + self.geometric_normal * OFFSET
+        } else {
+            self.p - self.geometric_normal * OFFSET
+        }
     }
 }
 
 pub struct HittableArray {
     imp: Vec<Arc<dyn Hittable + Send + Sync>>,
+    /// Emissive objects among `imp`
+    emitters: Vec<Arc<dyn Hittable + Send + Sync>>,
+    lights: Vec<Arc<dyn DeltaLight + Send + Sync>>,
+    background: Background,
+}
+
+impl Default for HittableArray {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HittableArray {
     pub fn new() -> HittableArray {
         HittableArray {
-            imp: Vec::new()
+            imp: Vec::new(),
+            emitters: Vec::new(),
+            lights: Vec::new(),
+            background: Background::default(),
         }
     }
 
-    pub fn len(&self) -> usize {
-        self.imp.len()
+    pub fn add(&mut self, obj: Arc<dyn Hittable + Send + Sync>) {
+        if obj.is_emissive() {
+            self.emitters.push(obj.clone());
+        }
+        self.imp.push(obj);
     }
 
-    pub fn empty(&self) -> bool {
-        self.imp.is_empty()
+    /// Emitters and the background when it is sampled for direct lighting
+    fn light_count(&self) -> usize {
+        self.emitters.len() + self.background.is_sampled() as usize
     }
 
-    pub fn clear(&mut self) {
-        self.imp.clear();
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
     }
 
-    pub fn add(&mut self, obj: Arc<dyn Hittable + Send + Sync>) {
-        self.imp.push(obj);
+    pub fn add_light(&mut self, light: Arc<dyn DeltaLight + Send + Sync>) {
+        self.lights.push(light);
     }
 }
 
@@ -78,7 +271,7 @@ impl Hittable for HittableArray {
         let mut result = None;
 
         for obj in &self.imp {
-            match obj.hit(&r, t_min, closest_so_far) {
+            match obj.hit(r, t_min, closest_so_far) {
                 None => {}
                 Some(rec) => {
                     closest_so_far = rec.t;
@@ -89,4 +282,32 @@ impl Hittable for HittableArray {
 
         result
     }
+
+    fn is_emissive(&self) -> bool {
+        self.light_count() > 0
+    }
+
+    /// Picks one of the emitters or the background uniformly
+    fn sample_direction(&self, origin: &Point3) -> Vec3 {
+        let count = self.light_count();
+        let index = ((random::<f32>() * count as f32) as usize).min(count - 1);
+        match self.emitters.get(index) {
+            Some(emitter) => emitter.sample_direction(origin),
+            None => self.background.sample_direction(),
+        }
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f32 {
+        let total: f32 = self.emitters.iter().map(|e| e.pdf_value(origin, direction)).sum::<f32>()
+            + self.background.pdf_value(direction);
+        total / self.light_count().max(1) as f32
+    }
+
+    fn delta_lights(&self) -> &[Arc<dyn DeltaLight + Send + Sync>] {
+        &self.lights
+    }
+
+    fn background(&self, direction: &Vec3) -> Color {
+        self.background.color(direction)
+    }
 }
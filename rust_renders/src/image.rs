@@ -0,0 +1,342 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use crate::Color;
+
+/// RGB raster used by image textures, row 0 is the top of the image
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Reads the next whitespace separated token of a PPM header, skipping comments
+fn read_token<R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut token = String::new();
+    let mut byte = [0u8; 1];
+    loop {
+        reader.read_exact(&mut byte)?;
+        match byte[0] {
+            b'#' if token.is_empty() => {
+                while byte[0] != b'\n' {
+                    reader.read_exact(&mut byte)?;
+                }
+            }
+            b if b.is_ascii_whitespace() => {
+                if !token.is_empty() {
+                    return Ok(token);
+                }
+            }
+            b => token.push(b as char),
+        }
+    }
+}
+
+fn read_number<R: Read>(reader: &mut R) -> io::Result<usize> {
+    read_token(reader)?.parse().map_err(|_| invalid_data("malformed number in PPM file"))
+}
+
+/// Reads one row of RGBE pixels into `scanline`. Rows are either stored flat or, for widths in
+/// 8..32768, as four separately run-length encoded channels after a `2 2 width` marker
+fn read_rgbe_scanline<R: Read>(reader: &mut R, scanline: &mut [u8]) -> io::Result<()> {
+    let width = scanline.len() / 4;
+    let mut marker = [0u8; 4];
+    reader.read_exact(&mut marker)?;
+    let encoded = (8..32768).contains(&width) && marker[0] == 2 && marker[1] == 2 && marker[2] < 128;
+    if !encoded {
+        scanline[..4].copy_from_slice(&marker);
+        return reader.read_exact(&mut scanline[4..]);
+    }
+    if ((marker[2] as usize) << 8 | marker[3] as usize) != width {
+        return Err(invalid_data("HDR scanline width mismatch"));
+    }
+
+    let mut byte = [0u8; 1];
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            reader.read_exact(&mut byte)?;
+            let (run, count) = if byte[0] > 128 { (true, byte[0] as usize - 128) } else { (false, byte[0] as usize) };
+            if count == 0 || x + count > width {
+                return Err(invalid_data("bad HDR run length"));
+            }
+            if run {
+                reader.read_exact(&mut byte)?;
+            }
+            for _ in 0..count {
+                if !run {
+                    reader.read_exact(&mut byte)?;
+                }
+                scanline[4 * x + channel] = byte[0];
+                x += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Image {
+        debug_assert_eq!(pixels.len(), width * height);
+        Image { width, height, pixels }
+    }
+
+    pub fn load_ppm<P: AsRef<Path>>(path: P) -> io::Result<Image> {
+        Image::from_ppm_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads plain (P3) or binary (P6) PPM, the format the renderer writes its output in
+    pub fn from_ppm_reader<R: Read>(mut reader: R) -> io::Result<Image> {
+        let magic = read_token(&mut reader)?;
+        let width = read_number(&mut reader)?;
+        let height = read_number(&mut reader)?;
+        let max_value = read_number(&mut reader)?;
+        if max_value == 0 || max_value > 65535 {
+            return Err(invalid_data("unsupported PPM maximum value"));
+        }
+        let scale = 1.0 / max_value as f32;
+
+        let mut samples = Vec::with_capacity(3 * width * height);
+        match magic.as_str() {
+            "P3" => {
+                for _ in 0..3 * width * height {
+                    samples.push(read_number(&mut reader)? as f32 * scale);
+                }
+            }
+            "P6" => {
+                let bytes_per_sample = if max_value < 256 { 1 } else { 2 };
+                let mut data = vec![0u8; 3 * width * height * bytes_per_sample];
+                reader.read_exact(&mut data)?;
+                for sample in data.chunks(bytes_per_sample) {
+                    let value = sample.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+                    samples.push(value as f32 * scale);
+                }
+            }
+            _ => return Err(invalid_data("not a PPM file")),
+        }
+
+        let pixels = samples.chunks(3).map(|c| Color::new(c[0], c[1], c[2])).collect();
+        Ok(Image::new(width, height, pixels))
+    }
+
+    pub fn load_hdr<P: AsRef<Path>>(path: P) -> io::Result<Image> {
+        Image::from_hdr_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads Radiance RGBE (.hdr) images, flat or run-length encoded, with the usual top to bottom rows
+    pub fn from_hdr_reader<R: BufRead>(mut reader: R) -> io::Result<Image> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with("#?") {
+            return Err(invalid_data("not a Radiance HDR file"));
+        }
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid_data("HDR header is not terminated"));
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return Err(invalid_data("unsupported HDR pixel format"));
+                }
+            }
+        }
+
+        line.clear();
+        reader.read_line(&mut line)?;
+        let resolution: Vec<&str> = line.split_whitespace().collect();
+        let (height, width) = match resolution.as_slice() {
+            ["-Y", height, "+X", width] => (height.parse::<usize>(), width.parse::<usize>()),
+            _ => return Err(invalid_data("unsupported HDR orientation")),
+        };
+        let (height, width) = (height.map_err(|_| invalid_data("malformed HDR height"))?,
+                               width.map_err(|_| invalid_data("malformed HDR width"))?);
+
+        let mut pixels = Vec::with_capacity(width * height);
+        let mut scanline = vec![0u8; 4 * width];
+        for _ in 0..height {
+            read_rgbe_scanline(&mut reader, &mut scanline)?;
+            pixels.extend(scanline.chunks(4).map(|rgbe| {
+                if rgbe[3] == 0 {
+                    return Color::new(0.0, 0.0, 0.0);
+                }
+                let scale = 2f32.powi(rgbe[3] as i32 - 136);
+                Color::new(rgbe[0] as f32 * scale, rgbe[1] as f32 * scale, rgbe[2] as f32 * scale)
+            }));
+        }
+        Ok(Image::new(width, height, pixels))
+    }
+
+    /// Converts sRGB encoded values, as stored in color images, into linear ones
+    pub fn decode_srgb(mut self) -> Image {
+        fn decode(c: f32) -> f32 {
+            if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        }
+        for pixel in self.pixels.iter_mut() {
+            *pixel = Color::new(decode(pixel[0]), decode(pixel[1]), decode(pixel[2]));
+        }
+        self
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Bilinearly filtered lookup with wrapping, `v` goes from the bottom of the image to the top
+    pub fn sample(&self, u: f32, v: f32) -> Color {
+        let x = u * self.width as f32 - 0.5;
+        let y = (1.0 - v) * self.height as f32 - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+
+        let wrap = |c: f32, size: usize| (c as i64).rem_euclid(size as i64) as usize;
+        let (x0, x1) = (wrap(x0, self.width), wrap(x0 + 1.0, self.width));
+        let (y0, y1) = (wrap(y0, self.height), wrap(y0 + 1.0, self.height));
+
+        self.pixel(x0, y0) * ((1.0 - tx) * (1.0 - ty))
+            + self.pixel(x1, y0) * (tx * (1.0 - ty))
+            + self.pixel(x0, y1) * ((1.0 - tx) * ty)
+            + self.pixel(x1, y1) * (tx * ty)
+    }
+
+    /// Half resolution version with 2x2 box filtering
+    fn downsample(&self) -> Image {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (x0, x1) = ((2 * x).min(self.width - 1), (2 * x + 1).min(self.width - 1));
+                let (y0, y1) = ((2 * y).min(self.height - 1), (2 * y + 1).min(self.height - 1));
+                pixels.push((self.pixel(x0, y0) + self.pixel(x1, y0) + self.pixel(x0, y1) + self.pixel(x1, y1)) * 0.25);
+            }
+        }
+        Image::new(width, height, pixels)
+    }
+}
+
+/// Prefiltered image pyramid, each level halves the resolution of the previous one down to a single pixel
+#[derive(Debug, Clone)]
+pub struct MipMap {
+    levels: Vec<Image>,
+}
+
+impl MipMap {
+    pub fn new(image: Image) -> MipMap {
+        let mut levels = vec![image];
+        loop {
+            let last = &levels[levels.len() - 1];
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.downsample();
+            levels.push(next);
+        }
+        MipMap { levels }
+    }
+
+    pub fn level(&self, level: usize) -> &Image {
+        &self.levels[level]
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Lookup blending the two levels closest to a filter `width` given in texture coordinates
+    pub fn trilinear(&self, u: f32, v: f32, width: f32) -> Color {
+        let base = &self.levels[0];
+        let texels = width * base.width.max(base.height) as f32;
+        let level = texels.max(1e-8).log2().clamp(0.0, (self.levels.len() - 1) as f32);
+        let l0 = level.floor() as usize;
+        let t = level - l0 as f32;
+        if t <= 0.0 || l0 + 1 >= self.levels.len() {
+            return self.levels[l0].sample(u, v);
+        }
+        self.levels[l0].sample(u, v) * (1.0 - t) + self.levels[l0 + 1].sample(u, v) * t
+    }
+
+    /// Lookup over the footprint spanned by the two texture space vectors: the level follows the minor axis
+    /// and several trilinear samples are taken along the major one, up to `max_anisotropy` of them
+    pub fn anisotropic(&self, u: f32, v: f32, d0: (f32, f32), d1: (f32, f32), max_anisotropy: f32) -> Color {
+        let length = |d: (f32, f32)| (d.0 * d.0 + d.1 * d.1).sqrt();
+        let (major, minor) = if length(d0) >= length(d1) { (d0, d1) } else { (d1, d0) };
+        let major_length = length(major);
+        if major_length <= 0.0 {
+            return self.levels[0].sample(u, v);
+        }
+        // Clamping the eccentricity blurs instead of taking an unbounded number of samples
+        let max_anisotropy = max_anisotropy.max(1.0);
+        let minor_length = length(minor).max(major_length / max_anisotropy);
+
+        let count = (major_length / minor_length).ceil() as usize;
+        let mut result = Color::new(0.0, 0.0, 0.0);
+        for i in 0..count {
+            let offset = (i as f32 + 0.5) / count as f32 - 0.5;
+            result += self.trilinear(u + major.0 * offset, v + major.1 * offset, minor_length);
+        }
+        result * (1.0 / count as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Color;
+    use crate::image::{Image, MipMap};
+
+    #[test]
+    fn reads_plain_and_binary_ppm() {
+        let plain = Image::from_ppm_reader("P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n".as_bytes()).unwrap();
+        let mut binary = b"P6 2 1 255\n".to_vec();
+        binary.extend_from_slice(&[255, 0, 0, 0, 0, 255]);
+        let binary = Image::from_ppm_reader(binary.as_slice()).unwrap();
+
+        for image in [plain, binary] {
+            assert_eq!((image.width, image.height), (2, 1));
+            assert_eq!(image.pixel(0, 0)[0], 1.0);
+            assert_eq!(image.pixel(1, 0)[2], 1.0);
+        }
+    }
+
+    #[test]
+    fn reads_flat_and_run_length_encoded_hdr() {
+        let mut flat = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+        flat.extend_from_slice(&[128, 64, 0, 129, 0, 0, 0, 0]);
+        let flat = Image::from_hdr_reader(flat.as_slice()).unwrap();
+        assert_eq!((flat.width, flat.height), (2, 1));
+        assert_eq!(flat.pixel(0, 0)[0], 1.0);
+        assert_eq!(flat.pixel(0, 0)[1], 0.5);
+        assert_eq!(flat.pixel(1, 0)[0], 0.0);
+
+        // Red as a run, green as literals, blue zero, shared exponent of 2^(130 - 136)
+        let mut encoded = b"#?RGBE\n\n-Y 1 +X 8\n".to_vec();
+        encoded.extend_from_slice(&[2, 2, 0, 8, 136, 64, 8, 0, 1, 2, 3, 4, 5, 6, 7, 136, 0, 136, 130]);
+        let encoded = Image::from_hdr_reader(encoded.as_slice()).unwrap();
+        assert_eq!(encoded.pixel(7, 0)[0], 1.0);
+        assert_eq!(encoded.pixel(7, 0)[1], 7.0 / 64.0);
+    }
+
+    #[test]
+    fn mip_levels_average_down_to_one_pixel() {
+        let pixels = (0..12).map(|i| Color::new(i as f32, 0.0, 0.0)).collect();
+        let mipmap = MipMap::new(Image::new(4, 3, pixels));
+        assert_eq!(mipmap.level_count(), 3);
+        assert_eq!((mipmap.level(1).width, mipmap.level(1).height), (2, 1));
+        assert_eq!(mipmap.level(1).pixel(0, 0)[0], 2.5);
+
+        // A footprint covering the whole texture hits the top level
+        assert!((mipmap.trilinear(0.3, 0.7, 1.0)[0] - mipmap.level(2).pixel(0, 0)[0]).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,244 @@
+use std::fmt::Debug;
+use rand::random;
+use crate::{Color, HittableArray, Vec3};
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+
+/// Scattering events along a single random walk through media between two surfaces, after which the
+/// walk goes on Russian roulette, ending on average after as many steps again where nothing is absorbed.
+/// Unlike cutting walks off, which darkened dense media that barely absorb, this is unbiased. The price
+/// is noise in such media, from the few long walks weighted up
+const MAX_MEDIUM_STEPS: u32 = 256;
+
+/// Algorithm computing the light arriving along camera rays. `PathTracer` is the only one so far,
+/// `ImageConfig::integrator` is where others would plug in
+pub trait Integrator: Debug {
+    fn radiance(&self, ray: &Ray, world: &HittableArray) -> Color;
+}
+
+/// Multiple importance sampling weight of a sample taken with density `pdf` against another strategy
+/// with density `other_pdf` for the same direction (Veach 1997). Favours the better strategy more than
+/// the balance heuristic, which helps where one of them is much noisier
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b <= 0.0 || !a.is_finite() { 1.0 } else { a / (a + b) }
+}
+
+/// Unidirectional path tracer sampling lights at every surface hit and weighting them against
+/// scattering with multiple importance sampling
+#[derive(Debug, Clone)]
+pub struct PathTracer {
+    /// Safety limit on the number of bounces, paths normally end through Russian roulette
+    max_depth: u32,
+    /// Bounces before paths start being terminated at random
+    roulette_depth: u32,
+}
+
+impl PathTracer {
+    /// Paths end after `max_depth` bounces at the latest. From `roulette_depth` bounces on they are
+    /// terminated at random, with the survivors weighted up to make up for it
+    pub fn new(max_depth: u32, roulette_depth: u32) -> PathTracer {
+        PathTracer { max_depth, roulette_depth }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, camera_ray: &Ray, world: &HittableArray) -> Color {
+        let mut ray = camera_ray.clone();
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+        // Weight of the path up to `ray`
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        // Density `ray` was scattered with, `None` for camera rays, delta lobes and media.
+        // Light of an emitter hit by a scattered ray is weighted against light sampling
+        let mut bsdf_pdf: Option<f32> = None;
+        let mut bounce = 0;
+        // Random walk inside of scattering media does not count towards the bounce depth
+        let mut medium_steps = 0;
+        while bounce < self.max_depth {
+            let mut rec = match world.hit(&ray, 0.00001, f32::INFINITY) {
+                Some(rec) => rec,
+                None => {
+                    let mut background = world.background(&ray.direction);
+                    if let Some(bsdf_pdf) = bsdf_pdf {
+                        background = background * power_heuristic(bsdf_pdf, world.pdf_value(&ray.origin, &ray.direction));
+                    }
+                    radiance += background * throughput;
+                    break;
+                }
+            };
+
+            let (scatter_distance, weight) = ray.media.sample(rec.t * ray.direction.length());
+            throughput = throughput * weight;
+            if let Some(distance) = scatter_distance {
+                medium_steps += 1;
+                if medium_steps > MAX_MEDIUM_STEPS && !survives_roulette(&mut throughput, 1.0 / MAX_MEDIUM_STEPS as f32) {
+                    break;
+                }
+                // Isotropic phase function
+                let origin = ray.at(distance / ray.direction.length());
+                ray = ray.spawn(origin, Vec3::rand_unit_sphere());
+                bsdf_pdf = None;
+                continue;
+            }
+            medium_steps = 0;
+
+            rec.compute_differentials(&ray);
+            let mut emitted = rec.material.emitted(&ray, &rec);
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                let light_pdf = world.pdf_value(&ray.origin, &ray.direction);
+                emitted = emitted * power_heuristic(bsdf_pdf, light_pdf);
+            }
+            radiance += (emitted + sample_light(&ray, world, &rec) + sample_delta_lights(&ray, world, &rec)) * throughput;
+
+            let scattered = match rec.material.sample(&ray, &rec) {
+                Some(scattered) => scattered,
+                None => break,
+            };
+            throughput = throughput * scattered.weight;
+            if bounce >= self.roulette_depth && !survives_roulette(&mut throughput, 0.05) {
+                break;
+            }
+
+            bsdf_pdf = if scattered.is_delta { None } else { Some(scattered.pdf) };
+            ray = scattered.ray;
+            bounce += 1;
+        }
+
+        radiance
+    }
+}
+
+/// Russian roulette ending paths that carry little light, and at least a `min_termination` share of
+/// the others, weighting up the survivors by the odds
+fn survives_roulette(throughput: &mut Color, min_termination: f32) -> bool {
+    let termination = (1.0 - throughput[0].max(throughput[1]).max(throughput[2])).max(min_termination);
+    if random::<f32>() < termination {
+        return false;
+    }
+    *throughput = *throughput * (1.0 / (1.0 - termination));
+    true
+}
+
+/// Light reaching the hit of `r_in` directly from an emitter or the background picked at random
+/// through a shadow ray, weighted against finding it by scattering
+fn sample_light<T: Hittable>(r_in: &Ray, world: &T, rec: &HitRecord) -> Color {
+    if !world.is_emissive() {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let direction = world.sample_direction(&rec.p).unit_vector();
+    let bsdf = rec.material.eval(r_in, rec, &direction);
+    let pdf = world.pdf_value(&rec.p, &direction);
+    // Delta lobes evaluate to nothing, saving the shadow ray
+    if pdf <= 0.0 || bsdf.luminance() <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    let shadow = r_in.spawn(rec.offset_origin(&direction), direction);
+    let radiance = match world.hit(&shadow, 0.00001, f32::INFINITY) {
+        Some(light_rec) if light_rec.material.is_emissive() => {
+            light_rec.material.emitted(&shadow, &light_rec) * shadow.media.transmittance(light_rec.t)
+        }
+        Some(_) => return Color::new(0.0, 0.0, 0.0),
+        // Escaping rays are not attenuated, same as scattered ones
+        None => world.background(&direction),
+    };
+    let weight = power_heuristic(pdf, rec.material.pdf(r_in, rec, &direction));
+    radiance * bsdf * (weight / pdf)
+}
+
+/// Light reaching the hit of `r_in` from every delta light. Scattering can never find these lights,
+/// so there is nothing to weight against
+fn sample_delta_lights<T: Hittable>(r_in: &Ray, world: &T, rec: &HitRecord) -> Color {
+    let mut result = Color::new(0.0, 0.0, 0.0);
+    for light in world.delta_lights() {
+        let sample = match light.illuminate(&rec.p) {
+            Some(sample) => sample,
+            None => continue,
+        };
+        let bsdf = rec.material.eval(r_in, rec, &sample.direction);
+        if bsdf.luminance() <= 0.0 {
+            continue;
+        }
+
+        let shadow = r_in.spawn(rec.offset_origin(&sample.direction), sample.direction);
+        if world.hit(&shadow, 0.00001, sample.distance).is_some() {
+            continue;
+        }
+        // Directional lights are outside of any medium the shadow ray could end in
+        let transmittance = if sample.distance.is_finite() {
+            shadow.media.transmittance(sample.distance)
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        };
+        result += sample.illuminance * bsdf * transmittance;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::{Color, HittableArray, Lambertian, Point3, Sphere, Vec3};
+    use crate::background::Background;
+    use crate::integrator::{Integrator, PathTracer};
+    use crate::material::Glass;
+    use crate::medium::Medium;
+    use crate::ray::Ray;
+
+    #[test]
+    fn furnace_sphere_reflects_its_albedo() {
+        let mut world = HittableArray::new();
+        world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))))));
+        world.set_background(Background::Constant(Color::new(1.0, 1.0, 1.0)));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 3.0), Vec3::new(0.1, 0.2, -1.0));
+
+        // A convex sphere is hit once, after which every path escapes
+        assert!((PathTracer::new(8, 8).radiance(&ray, &world)[0] - 0.5).abs() < 1e-5);
+
+        // Roulette ends half of the paths right away, but keeps the average
+        let roulette = PathTracer::new(8, 0);
+        let average = (0..10000).map(|_| roulette.radiance(&ray, &world)[0]).sum::<f32>() / 10000.0;
+        assert!((average - 0.5).abs() < 0.03);
+    }
+
+    #[test]
+    fn roulette_keeps_the_average_and_ends_paths() {
+        let mut world = HittableArray::new();
+        world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Arc::new(Lambertian::new(Color::new(0.1, 0.1, 0.1))))));
+        world.set_background(Background::Constant(Color::new(1.0, 1.0, 1.0)));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 3.0), Vec3::new(0.1, 0.2, -1.0));
+
+        // Nine out of ten paths end at the first hit, the rest carry ten times the weight
+        let roulette = PathTracer::new(8, 0);
+        let average = (0..20000).map(|_| roulette.radiance(&ray, &world)[0]).sum::<f32>() / 20000.0;
+        assert!((average - 0.1).abs() < 0.01, "{}", average);
+
+        // Inside of a white sphere nothing is ever absorbed, only roulette ends the paths
+        let mut closed = HittableArray::new();
+        closed.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Arc::new(Lambertian::new(Color::new(1.0, 1.0, 1.0))))));
+        let unlimited = PathTracer::new(u32::MAX, 0);
+        let inside = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        for _ in 0..100 {
+            assert_eq!(unlimited.radiance(&inside, &closed)[0], 0.0);
+        }
+    }
+
+    #[test]
+    fn long_medium_walks_keep_their_energy() {
+        // Without absorption every walk leaves the sphere again, many only after more than the step limit
+        let black = Color::new(0.0, 0.0, 0.0);
+        let fog = Medium::participating(1.0, black, Color::new(50.0, 50.0, 50.0), 0);
+        let mut world = HittableArray::new();
+        world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Arc::new(Glass::with_medium(fog)))));
+        world.set_background(Background::Constant(Color::new(1.0, 1.0, 1.0)));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
+
+        // The few walks surviving the roulette for long carry large weights, a median of batches ignores them
+        let tracer = PathTracer::new(64, 64);
+        let mut batches: Vec<f32> = (0..9)
+            .map(|_| (0..4000).map(|_| tracer.radiance(&ray, &world)[0]).sum::<f32>() / 4000.0)
+            .collect();
+        batches.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!((batches[4] - 1.0).abs() < 0.04, "{:?}", batches);
+    }
+}
@@ -0,0 +1,375 @@
+use std::sync::Arc;
+use rand::random;
+use crate::{Color, Vec3};
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::microfacet::{fresnel_dielectric, ggx_d, sample_ggx, smith_g1};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
+
+/// Blend of two materials, e.g. a decal over the surface it is applied to
+#[derive(Debug, Clone)]
+pub struct Mix {
+    first: Arc<dyn Material + Send + Sync>,
+    second: Arc<dyn Material + Send + Sync>,
+    /// Weight of the second material, luminance of the texture is used
+    mask: Arc<dyn Texture>,
+}
+
+impl Mix {
+    pub fn new(first: Arc<dyn Material + Send + Sync>,
+               second: Arc<dyn Material + Send + Sync>,
+               weight: f32)
+               -> Mix
+    {
+        Mix::masked(first, second, Arc::new(SolidColor::new(Color::new(weight, weight, weight))))
+    }
+
+    pub fn masked(first: Arc<dyn Material + Send + Sync>,
+                  second: Arc<dyn Material + Send + Sync>,
+                  mask: Arc<dyn Texture>)
+                  -> Mix
+    {
+        Mix { first, second, mask }
+    }
+}
+
+impl Material for Mix {
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        // Picking one of the materials by weight averages them out over the samples
+        let weight = self.mask.value(hit_rec).luminance().clamp(0.0, 1.0);
+        let mut record = if random::<f32>() < weight {
+            self.second.sample(r_in, hit_rec)?
+        } else {
+            self.first.sample(r_in, hit_rec)?
+        };
+        // Either material could have produced the direction
+        if !record.is_delta {
+            record.pdf = self.pdf(r_in, hit_rec, &record.ray.direction.unit_vector());
+        }
+        Some(record)
+    }
+
+    fn opacity(&self, hit_rec: &HitRecord) -> f32 {
+        let weight = self.mask.value(hit_rec).luminance().clamp(0.0, 1.0);
+        self.first.opacity(hit_rec) * (1.0 - weight) + self.second.opacity(hit_rec) * weight
+    }
+
+    fn emitted(&self, r_in: &Ray, hit_rec: &HitRecord) -> Color {
+        let weight = self.mask.value(hit_rec).luminance().clamp(0.0, 1.0);
+        self.first.emitted(r_in, hit_rec) * (1.0 - weight) + self.second.emitted(r_in, hit_rec) * weight
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.first.is_emissive() || self.second.is_emissive()
+    }
+
+    fn eval(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> Color {
+        let weight = self.mask.value(hit_rec).luminance().clamp(0.0, 1.0);
+        self.first.eval(r_in, hit_rec, direction) * (1.0 - weight) + self.second.eval(r_in, hit_rec, direction) * weight
+    }
+
+    fn pdf(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> f32 {
+        let weight = self.mask.value(hit_rec).luminance().clamp(0.0, 1.0);
+        self.first.pdf(r_in, hit_rec, direction) * (1.0 - weight) + self.second.pdf(r_in, hit_rec, direction) * weight
+    }
+}
+
+/// Dielectric coating over a base material, like varnish or the clear coat of car paint
+#[derive(Debug, Clone)]
+pub struct Coated {
+    base: Arc<dyn Material + Send + Sync>,
+    /// Refraction index of the coating
+    ref_idx: f32,
+    /// Roughness of the coating surface, zero for a perfect mirror
+    roughness: f32,
+    /// Fraction of light passing through the coating once at normal incidence
+    tint: Color,
+}
+
+impl Coated {
+    pub fn new(base: Arc<dyn Material + Send + Sync>, ref_idx: f32, roughness: f32) -> Coated {
+        Coated::tinted(base, ref_idx, roughness, Color::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn tinted(base: Arc<dyn Material + Send + Sync>,
+                  ref_idx: f32,
+                  roughness: f32,
+                  tint: Color)
+                  -> Coated
+    {
+        Coated { base, ref_idx, roughness, tint }
+    }
+
+    /// Path length inside the coating relative to its thickness, for a ray with the given cosine outside
+    fn path_length(&self, cosine: f32) -> f32 {
+        let sin2_t = (1.0 - cosine * cosine) / (self.ref_idx * self.ref_idx);
+        1.0 / (1.0 - sin2_t).max(1e-4).sqrt()
+    }
+
+    /// Fraction of the light passing into the coating at `cos_o`, through the base and out again at `cos_i`
+    fn transmittance(&self, cos_o: f32, cos_i: f32) -> Color {
+        let etai_over_etat = 1.0 / self.ref_idx;
+        let fresnel = (1.0 - fresnel_dielectric(cos_o, etai_over_etat)) * (1.0 - fresnel_dielectric(cos_i, etai_over_etat));
+        (self.tint.ln() * (self.path_length(cos_o) + self.path_length(cos_i))).exp() * fresnel
+    }
+
+    /// The ray refracted into the coating, which is what the base is lit and seen by
+    fn inner_ray(&self, r_in: &Ray, hit_rec: &HitRecord) -> Ray {
+        let etai_over_etat = 1.0 / self.ref_idx;
+        let direction = Vec3::refract(&r_in.direction.unit_vector(), &hit_rec.normal, etai_over_etat);
+        let differential = hit_rec.refracted_differential(r_in, &direction, etai_over_etat);
+        Ray { origin: r_in.origin, direction, media: r_in.media.clone(), differential }
+    }
+
+    /// Unit `direction` above the surface refracted into the coating
+    fn refract_in(&self, direction: &Vec3, n: &Vec3) -> Vec3 {
+        -Vec3::refract(&-*direction, n, 1.0 / self.ref_idx)
+    }
+
+    /// Unit `direction` inside of the coating refracted out of it, `None` where it is reflected back in
+    fn refract_out(&self, direction: &Vec3, n: &Vec3) -> Option<Vec3> {
+        let cos = direction.dot(*n);
+        let tangent = (*direction - *n * cos) * self.ref_idx;
+        let sin2 = tangent.length_squared();
+        if sin2 >= 1.0 {
+            return None;
+        }
+        Some(tangent + *n * (1.0 - sin2).sqrt())
+    }
+
+    /// Probability of sampling the reflection off the coating rather than the base
+    fn coat_probability(&self, cos_o: f32) -> f32 {
+        fresnel_dielectric(cos_o, 1.0 / self.ref_idx)
+    }
+
+    fn alpha(&self) -> f32 {
+        (self.roughness * self.roughness).max(1e-3)
+    }
+}
+
+impl Material for Coated {
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        let n = hit_rec.normal;
+        let wo = -r_in.direction.unit_vector();
+        let cos_o = wo.dot(n);
+        if cos_o <= 0.0 {
+            return None;
+        }
+        let coat_probability = self.coat_probability(cos_o);
+
+        let ray = if random::<f32>() < coat_probability {
+            if self.roughness <= 0.0 {
+                // Picked with the Fresnel reflectance of the mirror, which cancels its weight
+                let reflected = Vec3::reflect(&-wo, &n);
+                let mut ray = r_in.spawn(hit_rec.p, reflected);
+                ray.differential = hit_rec.reflected_differential(r_in, &reflected);
+                return Some(ScatterRecord::delta(ray, Color::new(1.0, 1.0, 1.0)));
+            }
+            let h = Onb::from_w(&n).local(&sample_ggx(self.alpha(), random(), random()));
+            r_in.spawn(hit_rec.p, Vec3::reflect(&-wo, &h))
+        } else {
+            // Light refracted into the coating reaches the base and has to get out through the coating again
+            let record = self.base.sample(&self.inner_ray(r_in, hit_rec), hit_rec)?;
+            let inner = record.ray.direction.unit_vector();
+            if inner.dot(n) <= 0.0 {
+                // Transmitted by the base into the object, the coating only covers the outside
+                let weight = record.weight * (self.tint.ln() * self.path_length(cos_o)).exp();
+                return Some(ScatterRecord { weight, pdf: record.pdf * (1.0 - coat_probability), ..record });
+            }
+            // Light reflected back in at the top of the coating isn't traced
+            let direction = self.refract_out(&inner, &n)?;
+            let ray = Ray { direction, ..record.ray };
+            if record.is_delta {
+                let weight = record.weight * self.transmittance(cos_o, direction.dot(n)) * (1.0 / (1.0 - coat_probability));
+                return Some(ScatterRecord::delta(ray, weight));
+            }
+            ray
+        };
+
+        let direction = ray.direction.unit_vector();
+        if direction.dot(n) <= 0.0 {
+            return None;
+        }
+        // Either layer could have produced the direction
+        let pdf = self.pdf(r_in, hit_rec, &direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let weight = self.eval(r_in, hit_rec, &direction) * (1.0 / pdf);
+        Some(ScatterRecord::new(ray, weight, pdf))
+    }
+
+    /// Microfacet reflection off the coating plus the base seen through it (Weidlich and Wilkie 2007)
+    fn eval(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> Color {
+        let n = hit_rec.normal;
+        let wo = -r_in.direction.unit_vector();
+        let (cos_o, cos_i) = (wo.dot(n), direction.dot(n));
+        if cos_o <= 0.0 || cos_i <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let coat = if self.roughness > 0.0 {
+            let alpha = self.alpha();
+            let h = (wo + *direction).unit_vector();
+            let fresnel = fresnel_dielectric(wo.dot(h), 1.0 / self.ref_idx);
+            ggx_d(h.dot(n), alpha) * smith_g1(cos_o, alpha) * smith_g1(cos_i, alpha) * fresnel / (4.0 * cos_o)
+        } else {
+            0.0
+        };
+
+        // The base includes the cosine inside of the coating, which is replaced by the one outside
+        let inner = self.refract_in(direction, &n);
+        let base = self.base.eval(&self.inner_ray(r_in, hit_rec), hit_rec, &inner) * (cos_i / inner.dot(n));
+        Color::new(coat, coat, coat) + base * self.transmittance(cos_o, cos_i)
+    }
+
+    fn pdf(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> f32 {
+        let n = hit_rec.normal;
+        let wo = -r_in.direction.unit_vector();
+        let (cos_o, cos_i) = (wo.dot(n), direction.dot(n));
+        if cos_o <= 0.0 || cos_i <= 0.0 {
+            return 0.0;
+        }
+        let coat_probability = self.coat_probability(cos_o);
+
+        let coat = if self.roughness > 0.0 {
+            let h = (wo + *direction).unit_vector();
+            ggx_d(h.dot(n), self.alpha()) * h.dot(n) / (4.0 * wo.dot(h).max(1e-4))
+        } else {
+            0.0
+        };
+
+        // Refraction squeezes the directions outside into a narrower cone inside
+        let inner = self.refract_in(direction, &n);
+        let jacobian = cos_i / (self.ref_idx * self.ref_idx * inner.dot(n));
+        let base = self.base.pdf(&self.inner_ray(r_in, hit_rec), hit_rec, &inner) * jacobian;
+        coat_probability * coat + (1.0 - coat_probability) * base
+    }
+
+    fn opacity(&self, hit_rec: &HitRecord) -> f32 {
+        self.base.opacity(hit_rec)
+    }
+
+    /// Emission of the base leaves through the coating once
+    fn emitted(&self, r_in: &Ray, hit_rec: &HitRecord) -> Color {
+        let emitted = self.base.emitted(r_in, hit_rec);
+        if emitted.luminance() <= 0.0 {
+            return emitted;
+        }
+        let cos_o = (-r_in.direction.unit_vector()).dot(hit_rec.normal).max(1e-4);
+        let transmittance = (self.tint.ln() * self.path_length(cos_o)).exp() * (1.0 - fresnel_dielectric(cos_o, 1.0 / self.ref_idx));
+        emitted * transmittance
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use std::sync::Arc;
+    use crate::{Color, HittableArray, Lambertian, Point3, Vec3};
+    use crate::background::Background;
+    use crate::furnace::{albedo, hit, integrate};
+    use crate::integrator::{Integrator, PathTracer};
+    use crate::layered::{Coated, Mix};
+    use crate::light::PointLight;
+    use crate::material::{Light, Material};
+    use crate::microfacet::fresnel_dielectric;
+    use crate::principled::Principled;
+    use crate::quad::Quad;
+    use crate::ray::Ray;
+
+    #[test]
+    fn layers_are_reciprocal() {
+        let base: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.3)));
+        let metal = Arc::new(Principled::from_metallic_roughness(Color::new(0.9, 0.9, 0.9), 1.0, 0.4));
+        let layers: [Arc<dyn Material>; 3] = [
+            Arc::new(Mix::new(base.clone(), metal, 0.3)),
+            Arc::new(Coated::new(base.clone(), 1.5, 0.0)),
+            Arc::new(Coated::tinted(base, 1.5, 0.3, Color::new(0.9, 0.7, 0.5))),
+        ];
+        let a = Vec3::new(0.3, 1.0, 0.1).unit_vector();
+        let b = Vec3::new(-0.6, 0.5, 0.4).unit_vector();
+        for material in &layers {
+            let (ray_a, rec_a) = hit(material.clone(), a);
+            let (ray_b, rec_b) = hit(material.clone(), b);
+            // `eval` includes the cosine of the incoming direction
+            let ab = material.eval(&ray_a, &rec_a, &b) * (1.0 / b[1]);
+            let ba = material.eval(&ray_b, &rec_b, &a) * (1.0 / a[1]);
+            for i in 0..3 {
+                assert!(ab[i] > 0.0);
+                assert!((ab[i] - ba[i]).abs() < 1e-4 * ab[i].max(1.0), "{:?} {:?}", ab, ba);
+            }
+        }
+    }
+
+    #[test]
+    fn coating_samples_agree_with_its_pdf() {
+        let base: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+        for &roughness in &[0.0, 0.3] {
+            let coated: Arc<dyn Material> = Arc::new(Coated::new(base.clone(), 1.5, roughness));
+            for &wo in &[Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.3, 0.0).unit_vector()] {
+                let sampled = albedo(coated.clone(), wo);
+                let mut integrated = integrate(coated.clone(), wo).0.luminance();
+                if roughness == 0.0 {
+                    // The mirror reflection is a delta lobe `eval` leaves out
+                    integrated += fresnel_dielectric(wo[1], 1.0 / 1.5);
+                }
+                // Directions reflected back in at the top of the coating make the samples noisy
+                assert!((sampled - integrated).abs() < 0.015, "{} {}", sampled, integrated);
+            }
+        }
+    }
+
+    #[test]
+    fn point_lights_shine_through_the_coating() {
+        let base: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut world = HittableArray::new();
+        world.add(Arc::new(Quad::new(Point3::new(-5.0, 0.0, 5.0), Vec3::new(10.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -10.0),
+                                     Arc::new(Coated::new(base, 1.5, 0.0)))));
+        world.set_background(Background::Constant(Color::new(0.0, 0.0, 0.0)));
+        world.add_light(Arc::new(PointLight::new(Point3::new(0.0, 2.0, 0.0), Color::new(1.0, 1.0, 1.0), 100.0)));
+
+        // Scattered rays only find the black sky, all light comes from the point light straight above
+        let ray = Ray::new(Point3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -1.0));
+        let radiance = PathTracer::new(4, 4).radiance(&ray, &world);
+        let transmittance = (1.0 - fresnel_dielectric(0.5f32.sqrt(), 1.0 / 1.5)) * (1.0 - fresnel_dielectric(1.0, 1.0 / 1.5));
+        let expected = 25.0 * 0.5 / PI * transmittance;
+        assert!((radiance[0] - expected).abs() < 1e-3 * expected, "{:?} {}", radiance, expected);
+    }
+
+    #[test]
+    fn layers_do_not_create_energy() {
+        let white: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian::new(Color::new(1.0, 1.0, 1.0)));
+        for &wo in &[Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.3, 0.0).unit_vector()] {
+            let smooth = albedo(Arc::new(Coated::new(white.clone(), 1.5, 0.0)), wo);
+            let rough = albedo(Arc::new(Coated::new(white.clone(), 1.5, 0.3)), wo);
+            let tinted = albedo(Arc::new(Coated::tinted(white.clone(), 1.5, 0.0, Color::new(0.5, 0.5, 0.5))), wo);
+            let mix = albedo(Arc::new(Mix::new(white.clone(), Arc::new(Lambertian::new(Color::new(0.2, 0.2, 0.2))), 0.5)), wo);
+            // Light leaving the base is partly reflected back into the coating, which isn't traced
+            assert!(smooth > 0.8 && smooth <= 1.01, "{}", smooth);
+            assert!(rough > 0.7 && rough <= 1.01, "{}", rough);
+            assert!(tinted < smooth);
+            assert!((mix - 0.6).abs() < 0.01, "{}", mix);
+        }
+    }
+
+    #[test]
+    fn coating_dims_the_emission_of_its_base() {
+        let lamp: Arc<dyn Material + Send + Sync> = Arc::new(Light::new(Color::new(2.0, 2.0, 2.0)));
+        let clear: Arc<dyn Material> = Arc::new(Coated::new(lamp.clone(), 1.5, 0.0));
+        let tinted: Arc<dyn Material> = Arc::new(Coated::tinted(lamp, 1.5, 0.0, Color::new(0.5, 1.0, 1.0)));
+        assert!(clear.is_emissive());
+
+        let (r_in, hit_rec) = hit(clear.clone(), Vec3::new(0.0, 1.0, 0.0));
+        // 4% is reflected back into the coating at normal incidence
+        assert!((clear.emitted(&r_in, &hit_rec)[0] - 1.92).abs() < 1e-4);
+        let emitted = tinted.emitted(&r_in, &hit_rec);
+        assert!((emitted[0] - 0.96).abs() < 1e-4 && (emitted[1] - 1.92).abs() < 1e-4);
+    }
+}
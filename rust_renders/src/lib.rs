@@ -0,0 +1,43 @@
+pub use crate::background::{Background, EnvironmentMap};
+pub use crate::camera::Camera;
+pub use crate::hittable::{HittableArray};
+pub use crate::image_config::ImageConfig;
+pub use crate::library::MaterialLibrary;
+pub use crate::material::{Lambertian, Light, Metal};
+pub use crate::render::render_fn;
+pub use crate::sphere::Sphere;
+pub use crate::vec3::{Color, Point3, Vec3};
+
+pub mod vec3;
+pub mod ray;
+pub mod hittable;
+pub mod sphere;
+pub mod camera;
+pub mod image_config;
+pub mod material;
+pub mod render;
+pub mod medium;
+pub mod onb;
+pub mod microfacet;
+pub mod principled;
+pub mod texture;
+pub mod layered;
+pub mod cloth;
+pub mod spectrum;
+pub mod thin_film;
+pub mod subsurface;
+pub mod merl;
+pub mod image;
+pub mod normal_map;
+pub mod cutout;
+pub mod quad;
+pub mod procedural;
+pub mod mapping;
+pub mod library;
+pub mod light;
+pub mod background;
+pub mod distribution;
+pub mod sky;
+pub mod integrator;
+#[cfg(test)]
+mod furnace;
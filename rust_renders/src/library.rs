@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use crate::Color;
+use crate::material::{Glass, Lambertian, Light, Material, Metal};
+use crate::merl::Merl;
+use crate::principled::{GltfMaterial, MtlMaterial, Principled, PrincipledParams};
+
+/// Named materials, so scenes can refer to a shared look instead of building materials inline
+#[derive(Debug, Clone, Default)]
+pub struct MaterialLibrary {
+    materials: HashMap<String, Arc<dyn Material + Send + Sync>>,
+}
+
+fn invalid_data(line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+/// Keys of a single material section in a library file
+struct Section {
+    name: String,
+    /// Line of the section header, for error messages
+    line: usize,
+    values: HashMap<String, String>,
+}
+
+impl Section {
+    fn take(&mut self, key: &str) -> Option<String> {
+        self.values.remove(key)
+    }
+
+    fn float(&mut self, key: &str, default: Option<f32>) -> io::Result<f32> {
+        match self.take(key) {
+            Some(value) => value.parse()
+                .map_err(|_| invalid_data(self.line, &format!("`{}` of `{}` is not a number", key, self.name))),
+            None => default.ok_or_else(|| invalid_data(self.line, &format!("`{}` is missing `{}`", self.name, key))),
+        }
+    }
+
+    fn unsigned(&mut self, key: &str, default: Option<u32>) -> io::Result<u32> {
+        match self.take(key) {
+            Some(value) => value.parse()
+                .map_err(|_| invalid_data(self.line, &format!("`{}` of `{}` is not a non-negative integer", key, self.name))),
+            None => default.ok_or_else(|| invalid_data(self.line, &format!("`{}` is missing `{}`", self.name, key))),
+        }
+    }
+
+    fn color(&mut self, key: &str, default: Option<Color>) -> io::Result<Color> {
+        let value = match self.take(key) {
+            Some(value) => value,
+            None => return default.ok_or_else(|| invalid_data(self.line, &format!("`{}` is missing `{}`", self.name, key))),
+        };
+        let channels: Vec<f32> = value.split_whitespace()
+            .map(|c| c.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid_data(self.line, &format!("`{}` of `{}` is not a color", key, self.name)))?;
+        match channels.as_slice() {
+            [gray] => Ok(Color::new(*gray, *gray, *gray)),
+            [r, g, b] => Ok(Color::new(*r, *g, *b)),
+            _ => Err(invalid_data(self.line, &format!("`{}` of `{}` needs one or three values", key, self.name))),
+        }
+    }
+
+    fn build(mut self) -> io::Result<Arc<dyn Material + Send + Sync>> {
+        let kind = self.take("type")
+            .ok_or_else(|| invalid_data(self.line, &format!("`{}` has no `type`", self.name)))?;
+        let material: Arc<dyn Material + Send + Sync> = match kind.as_str() {
+            "lambertian" => Arc::new(Lambertian::new(self.color("albedo", None)?)),
+            "metal" => Arc::new(Metal::new(self.color("albedo", None)?, self.float("roughness", Some(0.0))?)),
+            "glass" => {
+                let ior = self.float("ior", Some(1.5))?;
+                let glass = if self.values.contains_key("color") {
+                    Glass::tinted(ior, self.color("color", None)?, self.float("distance", Some(1.0))?)
+                } else {
+                    Glass::new(ior)
+                };
+                Arc::new(glass.with_priority(self.unsigned("priority", Some(0))?))
+            }
+            "light" => if self.values.contains_key("temperature") {
+                Arc::new(Light::blackbody(self.float("temperature", None)?, self.float("luminance", Some(1.0))?))
+            } else {
+                Arc::new(Light::new(self.color("color", None)?))
+            },
+            "principled" => {
+                let defaults = PrincipledParams::default();
+                Arc::new(Principled::new(PrincipledParams {
+                    base_color: self.color("base_color", Some(defaults.base_color))?,
+                    metallic: self.float("metallic", Some(defaults.metallic))?,
+                    roughness: self.float("roughness", Some(defaults.roughness))?,
+                    specular: self.float("specular", Some(defaults.specular))?,
+                    specular_tint: self.float("specular_tint", Some(defaults.specular_tint))?,
+                    sheen: self.float("sheen", Some(defaults.sheen))?,
+                    sheen_color: self.color("sheen_color", Some(defaults.sheen_color))?,
+                    sheen_tint: self.float("sheen_tint", Some(defaults.sheen_tint))?,
+                    clearcoat: self.float("clearcoat", Some(defaults.clearcoat))?,
+                    clearcoat_gloss: self.float("clearcoat_gloss", Some(defaults.clearcoat_gloss))?,
+                    transmission: self.float("transmission", Some(defaults.transmission))?,
+                    ior: self.float("ior", Some(defaults.ior))?,
+                }))
+            }
+            "measured" => {
+                let file = self.take("file")
+                    .ok_or_else(|| invalid_data(self.line, &format!("`{}` is missing `file`", self.name)))?;
+                let merl = Merl::load(&file)
+                    .map_err(|e| invalid_data(self.line, &format!("`{}` failed to read `{}`: {}", self.name, file, e)))?;
+                Arc::new(merl)
+            }
+            "gltf" => {
+                let defaults = GltfMaterial::default();
+                Arc::new(Principled::from_gltf(&GltfMaterial {
+                    base_color_factor: self.color("base_color", Some(defaults.base_color_factor))?,
+                    metallic_factor: self.float("metallic", Some(defaults.metallic_factor))?,
+                    roughness_factor: self.float("roughness", Some(defaults.roughness_factor))?,
+                    transmission_factor: self.float("transmission", Some(defaults.transmission_factor))?,
+                    ior: self.float("ior", Some(defaults.ior))?,
+                    specular_factor: self.float("specular", Some(defaults.specular_factor))?,
+                    clearcoat_factor: self.float("clearcoat", Some(defaults.clearcoat_factor))?,
+                    clearcoat_roughness_factor: self.float("clearcoat_roughness", Some(defaults.clearcoat_roughness_factor))?,
+                    sheen_color_factor: self.color("sheen_color", Some(defaults.sheen_color_factor))?,
+                }))
+            }
+            "mtl" => {
+                let defaults = MtlMaterial::default();
+                Arc::new(Principled::from_mtl(&MtlMaterial {
+                    diffuse: self.color("Kd", Some(defaults.diffuse))?,
+                    specular: self.color("Ks", Some(defaults.specular))?,
+                    shininess: self.float("Ns", Some(defaults.shininess))?,
+                    dissolve: self.float("d", Some(defaults.dissolve))?,
+                    optical_density: self.float("Ni", Some(defaults.optical_density))?,
+                }))
+            }
+            _ => return Err(invalid_data(self.line, &format!("`{}` has unknown type `{}`", self.name, kind))),
+        };
+
+        // Leftovers are most likely typos, which would otherwise silently fall back to defaults
+        if let Some(key) = self.values.keys().next() {
+            return Err(invalid_data(self.line, &format!("`{}` has unknown key `{}`", self.name, key)));
+        }
+        Ok(material)
+    }
+}
+
+impl MaterialLibrary {
+    pub fn new() -> MaterialLibrary {
+        MaterialLibrary { materials: HashMap::new() }
+    }
+
+    /// Library with the common materials: gold, copper, glass, water, diamond and rubber
+    pub fn with_presets() -> MaterialLibrary {
+        let mut library = MaterialLibrary::new();
+        library.insert("gold", Arc::new(Principled::from_metallic_roughness(Color::new(1.0, 0.766, 0.336), 1.0, 0.2)));
+        library.insert("copper", Arc::new(Principled::from_metallic_roughness(Color::new(0.955, 0.638, 0.538), 1.0, 0.25)));
+        // Containers win over the liquid touching their walls
+        library.insert("glass", Arc::new(Glass::new(1.5).with_priority(2)));
+        library.insert("water", Arc::new(Glass::new(1.333).with_priority(1)));
+        library.insert("diamond", Arc::new(Glass::new(2.418).with_priority(2)));
+        library.insert("rubber", Arc::new(Principled::new(PrincipledParams {
+            base_color: Color::new(0.02, 0.02, 0.02),
+            roughness: 0.8,
+            specular: 0.3,
+            ..PrincipledParams::default()
+        })));
+        library
+    }
+
+    /// Adds a material, replacing the one with the same name
+    pub fn insert(&mut self, name: &str, material: Arc<dyn Material + Send + Sync>) {
+        self.materials.insert(name.to_string(), material);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Material + Send + Sync>> {
+        self.materials.get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.materials.contains_key(name)
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Adds the materials described in INI-like sections, overriding existing ones with the same name:
+    ///
+    /// ```text
+    /// [brushed_steel]
+    /// type = metal
+    /// albedo = 0.6 0.6 0.62
+    /// roughness = 0.3
+    /// ```
+    ///
+    /// Types are `lambertian`, `metal`, `glass`, `light` and `principled`, keys follow the constructor parameters.
+    /// `gltf` takes the glTF factors without their `_factor` suffix, `mtl` the MTL statements `Kd`, `Ks`, `Ns`,
+    /// `d` and `Ni`. Both are converted to `principled`. `measured` reads a MERL BRDF from the binary given as `file`.
+    /// Nothing is added when the file has an error
+    pub fn read_from<R: BufRead>(&mut self, reader: R) -> io::Result<()> {
+        let mut sections: Vec<Section> = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(invalid_data(number, "empty material name"));
+                }
+                sections.push(Section { name: name.to_string(), line: number, values: HashMap::new() });
+                continue;
+            }
+
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| invalid_data(number, "expected `key = value`"))?;
+            let section = sections.last_mut()
+                .ok_or_else(|| invalid_data(number, "value outside of a material section"))?;
+            section.values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+
+        let materials = sections.into_iter()
+            .map(|section| Ok((section.name.clone(), section.build()?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        for (name, material) in materials {
+            self.materials.insert(name, material);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::library::MaterialLibrary;
+
+    #[test]
+    fn reads_materials_and_reports_errors() {
+        let mut library = MaterialLibrary::with_presets();
+        let file = "# shared look\n[steel]\ntype = metal\nalbedo = 0.6 0.6 0.62\nroughness = 0.3\n\n[gold]\ntype = lambertian\nalbedo = 0.9\n";
+        library.read_from(file.as_bytes()).unwrap();
+        assert!(library.contains("steel"));
+        assert!(library.contains("water"));
+        assert!(format!("{:?}", library.get("gold").unwrap()).starts_with("Lambertian"));
+
+        let typo = "[paint]\ntype = principled\nroughnes = 0.3\n";
+        let error = library.read_from(typo.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("roughnes"));
+        assert!(!library.contains("paint"));
+
+        library.read_from("[ice]\ntype = glass\nior = 1.31\npriority = 3\n".as_bytes()).unwrap();
+        assert!(library.contains("ice"));
+        for priority in &["-1", "1.5", "high"] {
+            let file = format!("[melt]\ntype = glass\npriority = {}\n", priority);
+            let error = library.read_from(file.as_bytes()).unwrap_err();
+            assert!(error.to_string().contains("priority"));
+        }
+        assert!(!library.contains("melt"));
+
+        let imported = "[leaf]\ntype = gltf\nbase_color = 0.2 0.5 0.1\nmetallic = 0\nsheen_color = 0.1 0.3 0\n\n[tile]\ntype = mtl\nKd = 0.7 0.6 0.5\nNs = 90\n";
+        library.read_from(imported.as_bytes()).unwrap();
+        assert!(format!("{:?}", library.get("leaf").unwrap()).starts_with("Principled"));
+        assert!(library.contains("tile"));
+        let error = library.read_from("[tile]\ntype = mtl\nNs = shiny\n".as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("Ns"));
+
+        let error = library.read_from("[velvet]\ntype = measured\nfile = missing.binary\n".as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("missing.binary"));
+    }
+}
@@ -0,0 +1,175 @@
+use std::f32::consts::PI;
+use std::fmt::Debug;
+use crate::{Color, Point3, Vec3};
+
+/// Light arriving at a point from a delta light
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// Unit vector towards the light
+    pub direction: Vec3,
+    /// Distance to the light, infinite for directional lights
+    pub distance: f32,
+    /// Illuminance in lux on a surface facing the light
+    pub illuminance: Color,
+}
+
+/// Lights without a surface, which rays can't hit and are only found through shadow rays.
+///
+/// Units follow the emitters: unit radiance is 1 cd/m^2, so intensities are in candela and
+/// illuminance in lux. Colors are scaled by the intensity and should have unit luminance
+pub trait DeltaLight: Debug {
+    /// `None` when the light doesn't reach `p` at all
+    fn illuminate(&self, p: &Point3) -> Option<LightSample>;
+}
+
+/// Light emitted equally in all directions from a single point
+#[derive(Debug, Clone)]
+pub struct PointLight {
+    position: Point3,
+    /// Luminous intensity in candela
+    intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, color: Color, candela: f32) -> PointLight {
+        PointLight { position, intensity: color * candela }
+    }
+
+    /// Point light with the given luminous flux, as printed on light bulbs
+    pub fn from_lumens(position: Point3, color: Color, lumens: f32) -> PointLight {
+        PointLight::new(position, color, lumens / (4.0 * PI))
+    }
+}
+
+/// Inverse square falloff from a point, `None` at the point itself
+fn from_point(position: &Point3, p: &Point3, intensity: Color) -> Option<(LightSample, Vec3)> {
+    let to_light = *position - *p;
+    let distance_squared = to_light.length_squared();
+    if distance_squared <= 0.0 {
+        return None;
+    }
+    let distance = distance_squared.sqrt();
+    let direction = to_light / distance;
+    Some((LightSample { direction, distance, illuminance: intensity * (1.0 / distance_squared) }, direction))
+}
+
+impl DeltaLight for PointLight {
+    fn illuminate(&self, p: &Point3) -> Option<LightSample> {
+        from_point(&self.position, p, self.intensity).map(|(sample, _)| sample)
+    }
+}
+
+/// Point light restricted to a cone, fading out smoothly between the inner and outer angle
+#[derive(Debug, Clone)]
+pub struct SpotLight {
+    position: Point3,
+    /// Unit axis of the cone
+    direction: Vec3,
+    /// Luminous intensity along the axis in candela
+    intensity: Color,
+    cos_inner: f32,
+    cos_outer: f32,
+}
+
+impl SpotLight {
+    /// Cone pointing from `position` to `target`, angles are measured from the axis in degrees
+    pub fn new(position: Point3,
+               target: Point3,
+               color: Color,
+               candela: f32,
+               inner_angle: f32,
+               outer_angle: f32)
+               -> SpotLight
+    {
+        let outer_angle = outer_angle.max(inner_angle);
+        SpotLight {
+            position,
+            direction: (target - position).unit_vector(),
+            intensity: color * candela,
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.to_radians().cos(),
+        }
+    }
+
+    /// Spot light with the given luminous flux, which stays the same when the cone is widened
+    pub fn from_lumens(position: Point3,
+                       target: Point3,
+                       color: Color,
+                       lumens: f32,
+                       inner_angle: f32,
+                       outer_angle: f32)
+                       -> SpotLight
+    {
+        // Solid angle of a cone halfway through the falloff approximates the smooth edge
+        let half_angle = 0.5 * (inner_angle + outer_angle.max(inner_angle));
+        let solid_angle = 2.0 * PI * (1.0 - half_angle.to_radians().cos());
+        SpotLight::new(position, target, color, lumens / solid_angle.max(1e-6), inner_angle, outer_angle)
+    }
+
+    fn falloff(&self, cos_theta: f32) -> f32 {
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl DeltaLight for SpotLight {
+    fn illuminate(&self, p: &Point3) -> Option<LightSample> {
+        let (mut sample, direction) = from_point(&self.position, p, self.intensity)?;
+        let falloff = self.falloff(-direction.dot(self.direction));
+        if falloff <= 0.0 {
+            return None;
+        }
+        sample.illuminance = sample.illuminance * falloff;
+        Some(sample)
+    }
+}
+
+/// Parallel light from infinitely far away, like the sun
+#[derive(Debug, Clone)]
+pub struct DirectionalLight {
+    /// Unit vector towards the light
+    to_light: Vec3,
+    illuminance: Color,
+}
+
+impl DirectionalLight {
+    /// Light travelling along `direction`, with the illuminance in lux on a surface facing it.
+    /// Direct sunlight is around 100000 lux
+    pub fn new(direction: Vec3, color: Color, lux: f32) -> DirectionalLight {
+        DirectionalLight { to_light: -direction.unit_vector(), illuminance: color * lux }
+    }
+}
+
+impl DeltaLight for DirectionalLight {
+    fn illuminate(&self, _p: &Point3) -> Option<LightSample> {
+        Some(LightSample { direction: self.to_light, distance: f32::INFINITY, illuminance: self.illuminance })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Color, Point3};
+    use crate::light::{DeltaLight, PointLight, SpotLight};
+
+    #[test]
+    fn intensity_falls_off_with_distance_and_cone() {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let bulb = PointLight::new(Point3::new(0.0, 2.0, 0.0), white, 100.0);
+        let sample = bulb.illuminate(&Point3::new(0.0, 0.0, 0.0)).unwrap();
+        assert!((sample.illuminance[0] - 25.0).abs() < 1e-4);
+        assert!((sample.direction[1] - 1.0).abs() < 1e-6);
+
+        let spot = SpotLight::new(Point3::new(0.0, 2.0, 0.0), Point3::new(0.0, 0.0, 0.0), white, 100.0, 20.0, 30.0);
+        assert!((spot.illuminate(&Point3::new(0.0, 0.0, 0.0)).unwrap().illuminance[0] - 25.0).abs() < 1e-4);
+        // 45 degrees off the axis
+        assert!(spot.illuminate(&Point3::new(2.0, 0.0, 0.0)).is_none());
+        let edge = spot.illuminate(&Point3::new(2.0 * 25f32.to_radians().tan(), 0.0, 0.0)).unwrap();
+        assert!(edge.illuminance[0] > 0.0 && edge.illuminance[0] < 25.0);
+    }
+}
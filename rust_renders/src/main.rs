@@ -1,29 +1,36 @@
 use std::borrow::Borrow;
 use std::sync::Arc;
-use crate::camera::Camera;
-use crate::hittable::{HittableArray};
-use crate::image_config::ImageConfig;
-use crate::material::{Glass, Lambertian, Light, Material, Metal};
-use crate::render::render_fn;
-use crate::sphere::Sphere;
-use crate::vec3::{Color, Point3, Vec3};
+use rust_renders::background::{Background, EnvironmentMap};
+use rust_renders::camera::Camera;
+use rust_renders::hittable::{HittableArray};
+use rust_renders::image_config::{self, ImageConfig};
+use rust_renders::integrator::PathTracer;
+use rust_renders::library::MaterialLibrary;
+use rust_renders::material::{Lambertian, Light, Metal};
+use rust_renders::render::render_fn;
+use rust_renders::sphere::Sphere;
+use rust_renders::vec3::{Color, Point3, Vec3};
 
-mod vec3;
-mod ray;
-mod hittable;
-mod sphere;
-mod camera;
-mod image_config;
-mod material;
-mod render;
+/// Non-negative integer from the environment variable `name`, `default` when it is not set
+fn env_u32(name: &str, default: u32) -> u32 {
+    match std::env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| panic!("{} must be a non-negative integer", name)),
+        Err(_) => default,
+    }
+}
 
 fn main() {
-    let mut config = Arc::new(ImageConfig::default_config());
+    let mut config = ImageConfig::default_config();
+    // Deep glass or dense fog may need longer paths, e.g. `MAX_DEPTH=64 ROULETTE_DEPTH=8`
+    let max_depth = env_u32("MAX_DEPTH", image_config::MAX_DEPTH);
+    let roulette_depth = env_u32("ROULETTE_DEPTH", image_config::ROULETTE_DEPTH);
+    config.integrator = Arc::new(PathTracer::new(max_depth, roulette_depth));
+    let config = Arc::new(config);
 
     let look_from = Point3::new(-2.0, 2.0, 1.0);
     let look_at = Point3::new(0.0, 0.0, -1.0);
 
-    let mut camera = Arc::new(Camera::new(
+    let camera = Arc::new(Camera::new(
         look_from,
         look_at,
         Vec3::new(0., 1., 0.),
@@ -33,23 +40,33 @@ fn main() {
         (look_from - Vec3::new(0.0, 0.0, -1.0)).length()
     ));
 
-    let ground_material
-        = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
-    let center_sphere_material
-        = Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
-    let left_sphere_material
-        = Arc::new(Glass::new(1.5));
-    let right_sphere_material
-        = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.3), 1.0));
-    let light_source_material
-        = Arc::new(Light::new(Color::new(0.999, 0.996, 0.95)));
+    let mut materials = MaterialLibrary::with_presets();
+    materials.insert("ground", Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0))));
+    materials.insert("blue", Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5))));
+    materials.insert("bronze", Arc::new(Metal::new(Color::new(0.8, 0.6, 0.3), 1.0)));
+    materials.insert("lamp", Arc::new(Light::new(Color::new(0.999, 0.996, 0.95))));
+    // A library file passed on the command line overrides the materials above by name
+    if let Some(path) = std::env::args().nth(1) {
+        materials.load(&path).expect("failed to load the material library");
+    }
+    let material = |name: &str| materials.get(name).expect("material missing from the library");
 
     let mut world = HittableArray::new();
-    world.add(Arc::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, ground_material)));
-    world.add(Arc::new(Sphere::new(Point3::new(0.0,    0.0, -1.0), 0.5, center_sphere_material)));
-    world.add(Arc::new(Sphere::new(Point3::new(-1.0,   0.0, -1.0), 0.5, left_sphere_material)));
-    world.add(Arc::new(Sphere::new(Point3::new(1.0,    0.0, -1.0), 0.5, right_sphere_material)));
-    world.add(Arc::new(Sphere::new(Point3::new(3.0,    0.0,  1.0), 0.5, light_source_material)));
+    world.add(Arc::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, material("ground"))));
+    world.add(Arc::new(Sphere::new(Point3::new(0.0,    0.0, -1.0), 0.5, material("blue"))));
+    world.add(Arc::new(Sphere::new(Point3::new(-1.0,   0.0, -1.0), 0.5, material("glass"))));
+    world.add(Arc::new(Sphere::new(Point3::new(1.0,    0.0, -1.0), 0.5, material("bronze"))));
+    world.add(Arc::new(Sphere::new(Point3::new(3.0,    0.0,  1.0), 0.5, material("lamp"))));
+    // An equirectangular HDR image as the second argument replaces the sky, optionally turned by the
+    // degrees of the third and scaled by the fourth
+    if let Some(path) = std::env::args().nth(2) {
+        let argument = |n: usize, default: f32| std::env::args().nth(n)
+            .map_or(default, |value| value.parse().expect("environment rotation and intensity must be numbers"));
+        let environment = EnvironmentMap::load(&path).expect("failed to load the environment map")
+            .with_rotation(argument(3, 0.0))
+            .with_intensity(argument(4, 1.0));
+        world.set_background(Background::Environment(environment));
+    }
     let world = Arc::new(world);
 
     let result = render_fn(config.clone(), camera, world);
@@ -0,0 +1,136 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+use crate::{Color, Point3, Vec3};
+use crate::hittable::HitRecord;
+use crate::onb::Onb;
+use crate::texture::Texture;
+
+/// Placement of a projection in the scene
+#[derive(Debug, Clone)]
+pub struct TextureTransform {
+    origin: Point3,
+    /// The projection axis is `w`
+    frame: Onb,
+    /// World space size of one texture repeat
+    size: f32,
+}
+
+impl TextureTransform {
+    /// Projection along the z axis from the world origin, one repeat per unit
+    pub fn identity() -> TextureTransform {
+        TextureTransform::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0)
+    }
+
+    pub fn new(origin: Point3, axis: Vec3, size: f32) -> TextureTransform {
+        TextureTransform { origin, frame: Onb::from_w(&axis.unit_vector()), size }
+    }
+
+    fn to_local(&self, p: &Point3) -> Vec3 {
+        self.frame.to_local(&(*p - self.origin)) / self.size
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    /// Onto the plane orthogonal to the axis
+    Planar,
+    /// Around the axis, `u` goes around and `v` along it
+    Cylindrical,
+    /// Longitude and latitude with the poles on the axis
+    Spherical,
+}
+
+impl Projection {
+    fn uv(&self, local: &Vec3) -> (f32, f32) {
+        let (x, y, z) = (local[0], local[1], local[2]);
+        let around = || (y.atan2(x) + PI) / (2.0 * PI);
+        match self {
+            Projection::Planar => (x, y),
+            Projection::Cylindrical => (around(), z),
+            Projection::Spherical => {
+                let length = local.length();
+                let theta = if length > 0.0 { (z / length).clamp(-1.0, 1.0).acos() } else { 0.0 };
+                (around(), 1.0 - theta / PI)
+            }
+        }
+    }
+}
+
+/// Replaces the surface coordinates of the hit with projected ones before looking up the texture,
+/// for shapes without usable coordinates of their own
+#[derive(Debug, Clone)]
+pub struct Projected {
+    texture: Arc<dyn Texture>,
+    projection: Projection,
+    transform: TextureTransform,
+}
+
+impl Projected {
+    pub fn new(texture: Arc<dyn Texture>, projection: Projection, transform: TextureTransform) -> Projected {
+        Projected { texture, projection, transform }
+    }
+}
+
+impl Texture for Projected {
+    fn value(&self, hit_rec: &HitRecord) -> Color {
+        let mut projected = hit_rec.clone();
+        let (u, v) = self.projection.uv(&self.transform.to_local(&hit_rec.p));
+        projected.u = u;
+        projected.v = v;
+        self.texture.value(&projected)
+    }
+}
+
+/// Three planar projections along the axes of the transform, blended by the normal
+#[derive(Debug, Clone)]
+pub struct Triplanar {
+    texture: Arc<dyn Texture>,
+    transform: TextureTransform,
+    /// Higher values narrow the blend regions between projections
+    sharpness: f32,
+}
+
+impl Triplanar {
+    pub fn new(texture: Arc<dyn Texture>, transform: TextureTransform, sharpness: f32) -> Triplanar {
+        Triplanar { texture, transform, sharpness }
+    }
+}
+
+impl Texture for Triplanar {
+    fn value(&self, hit_rec: &HitRecord) -> Color {
+        let local = self.transform.to_local(&hit_rec.p);
+        let normal = self.transform.frame.to_local(&hit_rec.normal);
+        let weights = [0, 1, 2].map(|i| normal[i].abs().powf(self.sharpness));
+        let total = weights[0] + weights[1] + weights[2];
+        if total <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let planes = [(local[1], local[2]), (local[0], local[2]), (local[0], local[1])];
+        let mut projected = hit_rec.clone();
+        let mut result = Color::new(0.0, 0.0, 0.0);
+        for (weight, (u, v)) in weights.iter().zip(planes) {
+            if *weight > 0.0 {
+                projected.u = u;
+                projected.v = v;
+                result += self.texture.value(&projected) * (weight / total);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Vec3;
+    use crate::mapping::Projection;
+
+    #[test]
+    fn spherical_projection_puts_poles_on_axis() {
+        let (_, v) = Projection::Spherical.uv(&Vec3::new(0.0, 0.0, 2.0));
+        assert_eq!(v, 1.0);
+        let (u, v) = Projection::Cylindrical.uv(&Vec3::new(-1.0, 0.0, 0.25));
+        assert!((u - 1.0).abs() < 1e-6);
+        assert_eq!(v, 0.25);
+    }
+}
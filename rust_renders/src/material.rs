@@ -1,80 +1,233 @@
+use std::f32::consts::PI;
 use std::fmt::Debug;
+use std::sync::Arc;
 use rand::random;
 use crate::{Color, Vec3};
 use crate::hittable::HitRecord;
+use crate::medium::Medium;
+use crate::onb::Onb;
+use crate::spectrum::blackbody;
+use crate::thin_film::{conductor_ior, ThinFilm};
 use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
+
+/// Continuation of a path sampled by a material
+#[derive(Debug, Clone)]
+pub struct ScatterRecord {
+    pub ray: Ray,
+    /// BSDF times the cosine over the density of the sampled direction
+    pub weight: Color,
+    /// Solid angle density of the sampled direction, meaningless for delta lobes
+    pub pdf: f32,
+    /// Sampled from a lobe `eval` and `pdf` do not cover, like a perfect mirror or refraction.
+    /// Light sampling can't find such directions, so emitters they hit are counted in full
+    pub is_delta: bool,
+}
+
+impl ScatterRecord {
+    pub fn new(ray: Ray, weight: Color, pdf: f32) -> ScatterRecord {
+        ScatterRecord { ray, weight, pdf, is_delta: false }
+    }
+
+    pub fn delta(ray: Ray, weight: Color) -> ScatterRecord {
+        ScatterRecord { ray, weight, pdf: 0.0, is_delta: true }
+    }
+}
 
 pub trait Material: Debug + Send {
-    fn scatter(&self,
-               r_in: &Ray,
-               hit_rec: &HitRecord,
-               attenuation: &mut Color,
-               r_out: &mut Ray)
-               -> bool;
+    /// Picks a direction to continue the path in, `None` when the light is absorbed
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord>;
+
+    /// Fraction of the light arriving from the unit `direction` which is scattered back along the ray,
+    /// the BSDF times the cosine. Delta lobes are not included
+    fn eval(&self, _r_in: &Ray, _hit_rec: &HitRecord, _direction: &Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Solid angle density of `sample` picking the unit `direction` from the lobes `eval` covers
+    fn pdf(&self, _r_in: &Ray, _hit_rec: &HitRecord, _direction: &Vec3) -> f32 {
+        0.0
+    }
+
+    /// Coverage of the surface, intersection routines skip hits where the surface is cut out
+    fn opacity(&self, _hit_rec: &HitRecord) -> f32 {
+        1.0
+    }
+
+    /// Radiance the surface emits back along the ray
+    fn emitted(&self, _r_in: &Ray, _hit_rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Emissive objects are sampled directly when lighting other surfaces
+    fn is_emissive(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Lambertian {
+        Lambertian::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Lambertian {
         Lambertian { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self,
-               _r_in: &Ray,
-               hit_rec: &HitRecord,
-               attenuation: &mut Color,
-               r_out: &mut Ray)
-               -> bool
-    {
-        let scatter_direction = hit_rec.normal + Vec3::rand_unit_sphere();
-        *r_out = Ray::new(hit_rec.p, scatter_direction);
-        *attenuation = self.albedo.clone();
-        true
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        let direction = Onb::from_w(&hit_rec.normal).local(&Vec3::rand_cosine_direction());
+        let pdf = self.pdf(r_in, hit_rec, &direction);
+        // Cosine-weighted sampling cancels everything but the albedo
+        Some(ScatterRecord::new(r_in.spawn(hit_rec.p, direction), self.albedo.value(hit_rec), pdf))
+    }
+
+    fn eval(&self, _r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> Color {
+        let cosine = direction.dot(hit_rec.normal).max(0.0);
+        self.albedo.value(hit_rec) * (cosine / PI)
+    }
+
+    fn pdf(&self, _r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> f32 {
+        direction.dot(hit_rec.normal).max(0.0) / PI
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Metal {
-    albedo: Color,
-    fuzz: f32,
+    albedo: Arc<dyn Texture>,
+    /// Roughness, luminance of the texture is used
+    fuzz: Arc<dyn Texture>,
+    film: Option<ThinFilm>,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f32) -> Metal {
-        Metal { albedo, fuzz }
+        Metal::textured(Arc::new(SolidColor::new(albedo)),
+                        Arc::new(SolidColor::new(Color::new(fuzz, fuzz, fuzz))))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, fuzz: Arc<dyn Texture>) -> Metal {
+        Metal { albedo, fuzz, film: None }
+    }
+
+    /// Coats the metal with a thin film, like oxide layers on heated steel
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Metal {
+        self.film = Some(film);
+        self
+    }
+
+    /// Reflectance for the incoming ray, the same for all the fuzzed directions
+    fn reflectance(&self, r_in: &Ray, hit_rec: &HitRecord) -> Color {
+        let albedo = self.albedo.value(hit_rec);
+        match &self.film {
+            Some(film) => {
+                let (eta, k) = conductor_ior(&albedo, &albedo);
+                let cos_theta = (-r_in.direction.unit_vector()).dot(hit_rec.normal).min(1.0);
+                film.conductor_reflectance(hit_rec, cos_theta, r_in.media.ref_idx(), &eta, &k)
+            }
+            None => albedo,
+        }
+    }
+
+    /// Density of the unit `direction` pointing along `reflected + fuzz * s`, with `s` uniform on the unit sphere.
+    /// The direction crosses the sphere of fuzzed endpoints up to twice, each crossing adds its area density
+    /// converted into solid angle
+    fn fuzz_pdf(reflected: &Vec3, fuzz: f32, direction: &Vec3) -> f32 {
+        let b = direction.dot(*reflected);
+        let discriminant = b * b - reflected.length_squared() + fuzz * fuzz;
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let root = discriminant.sqrt();
+        [b - root, b + root].iter()
+            .filter(|&&t| t > 0.0)
+            .map(|t| t * t / (4.0 * PI * fuzz * root))
+            .sum()
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self,
-               r_in: &Ray,
-               hit_rec: &HitRecord,
-               attenuation: &mut Color,
-               r_out: &mut Ray)
-               -> bool
-    {
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        let reflected = Vec3::reflect(&r_in.direction.unit_vector(), &hit_rec.normal);
+        let fuzz = self.fuzz.value(hit_rec).luminance();
+        let reflectance = self.reflectance(r_in, hit_rec);
+        if fuzz <= 0.0 {
+            let mut ray = r_in.spawn(hit_rec.p, reflected);
+            ray.differential = hit_rec.reflected_differential(r_in, &reflected);
+            return Some(ScatterRecord::delta(ray, reflectance));
+        }
+
+        let direction = reflected + Vec3::rand_unit_sphere() * fuzz;
+        if direction.dot(hit_rec.normal) <= 0.0 {
+            return None;
+        }
+        let pdf = Metal::fuzz_pdf(&reflected, fuzz, &direction.unit_vector());
+        Some(ScatterRecord::new(r_in.spawn(hit_rec.p, direction), reflectance, pdf))
+    }
+
+    /// Fuzzed directions below the surface are absorbed, the rest keep the reflectance
+    fn eval(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> Color {
+        self.reflectance(r_in, hit_rec) * self.pdf(r_in, hit_rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> f32 {
+        let fuzz = self.fuzz.value(hit_rec).luminance();
+        if fuzz <= 0.0 || direction.dot(hit_rec.normal) <= 0.0 {
+            return 0.0;
+        }
         let reflected = Vec3::reflect(&r_in.direction.unit_vector(), &hit_rec.normal);
-        *r_out = Ray::new(hit_rec.p, reflected + Vec3::rand_unit_sphere() * self.fuzz);
-        *attenuation = self.albedo.clone();
-        r_out.direction.dot(hit_rec.normal) > 0.0
+        Metal::fuzz_pdf(&reflected, fuzz, direction)
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Glass {
-    /// Refraction index
-    ref_idx: f32,
+    /// Medium enclosed by the glass surface
+    medium: Medium,
+    film: Option<ThinFilm>,
 }
 
 impl Glass {
     pub fn new(ref_idx: f32) -> Glass {
-        Glass { ref_idx }
+        Glass::with_absorption(ref_idx, Color::new(0.0, 0.0, 0.0))
+    }
+
+    pub fn with_absorption(ref_idx: f32, absorption: Color) -> Glass {
+        Glass::with_medium(Medium::new(ref_idx, absorption, 0))
+    }
+
+    /// Smooth dielectric boundary of an arbitrary medium
+    pub fn with_medium(medium: Medium) -> Glass {
+        Glass { medium, film: None }
+    }
+
+    /// Colored glass which transmits `color` after travelling `distance` inside the medium.
+    /// Black channels still let a tiny fraction through, and `distance` is kept positive
+    pub fn tinted(ref_idx: f32, color: Color, distance: f32) -> Glass {
+        const MIN_TRANSMITTANCE: f32 = 1e-6;
+        let color = Color::new(color[0].clamp(MIN_TRANSMITTANCE, 1.0),
+                               color[1].clamp(MIN_TRANSMITTANCE, 1.0),
+                               color[2].clamp(MIN_TRANSMITTANCE, 1.0));
+        Glass::with_absorption(ref_idx, color.ln() * (-1.0 / distance.max(1e-6)))
+    }
+
+    /// Sets the priority used where this volume overlaps with other dielectrics,
+    /// e.g. a glass should win over the liquid slightly intersecting its walls
+    pub fn with_priority(mut self, priority: u32) -> Glass {
+        self.medium.priority = priority;
+        self
+    }
+
+    /// Coats the surface with a thin film, like a soap bubble
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Glass {
+        self.film = Some(film);
+        self
     }
 
     fn shlick_probability(cosine: f32, ref_idx: f32) -> f32 {
@@ -85,31 +238,51 @@ impl Glass {
 }
 
 impl Material for Glass {
-    fn scatter(&self,
-               r_in: &Ray,
-               hit_rec: &HitRecord,
-               attenuation: &mut Color,
-               r_out: &mut Ray)
-               -> bool
-    {
-        *attenuation = Color::new(1.0, 1.0, 1.0);
-        let etai_over_etat = if hit_rec.front_face { 1.0 / self.ref_idx } else { self.ref_idx };
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        let media = r_in.media.crossing(&self.medium, hit_rec.front_face);
+
+        // Surface is inside of a volume with higher priority, so there is no interface at all
+        if r_in.media.same_current(&media) {
+            let ray = Ray { origin: hit_rec.p, direction: r_in.direction, media, differential: r_in.differential };
+            return Some(ScatterRecord::delta(ray, Color::new(1.0, 1.0, 1.0)));
+        }
+
+        let etai_over_etat = r_in.media.ref_idx() / media.ref_idx();
         let unit_direction = r_in.direction.unit_vector();
 
         let cos_theta = (-unit_direction).dot(hit_rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
-        if etai_over_etat * sin_theta > 1.0
-            || random::<f32>() < Glass::shlick_probability(cos_theta, self.ref_idx) {
+        let mut weight = Color::new(1.0, 1.0, 1.0);
+        let total_internal_reflection = etai_over_etat * sin_theta > 1.0;
+        let reflect = match &self.film {
+            // Nothing refracts, whatever the film does to the phase
+            Some(_) if total_internal_reflection => true,
+            Some(film) => {
+                let reflectance = film.dielectric_reflectance(hit_rec, cos_theta, r_in.media.ref_idx(), media.ref_idx());
+                let probability = (reflectance[0] + reflectance[1] + reflectance[2]) / 3.0;
+                let reflect = probability >= 1.0 || random::<f32>() < probability;
+                weight = if reflect {
+                    reflectance * (1.0 / probability)
+                } else {
+                    (Color::new(1.0, 1.0, 1.0) - reflectance) * (1.0 / (1.0 - probability))
+                };
+                reflect
+            }
+            None => total_internal_reflection
+                || random::<f32>() < Glass::shlick_probability(cos_theta, etai_over_etat),
+        };
+
+        if reflect {
             let reflected = Vec3::reflect(&unit_direction, &hit_rec.normal);
-            *r_out = Ray::new(hit_rec.p, reflected);
-            return true;
+            let mut ray = r_in.spawn(hit_rec.p, reflected);
+            ray.differential = hit_rec.reflected_differential(r_in, &reflected);
+            return Some(ScatterRecord::delta(ray, weight));
         }
 
         let refracted = Vec3::refract(&unit_direction, &hit_rec.normal, etai_over_etat);
-        *r_out = Ray::new(hit_rec.p, refracted);
-
-        true
+        let differential = hit_rec.refracted_differential(r_in, &refracted, etai_over_etat);
+        Some(ScatterRecord::delta(Ray { origin: hit_rec.p, direction: refracted, media, differential }, weight))
     }
 }
 
@@ -119,9 +292,16 @@ pub struct Light {
 }
 
 impl Material for Light {
-    fn scatter(&self, _r_in: &Ray, _hit_rec: &HitRecord, attenuation: &mut Color, r_out: &mut Ray) -> bool {
-        *attenuation = self.col.clone();
-        false
+    fn sample(&self, _r_in: &Ray, _hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, _r_in: &Ray, _hit_rec: &HitRecord) -> Color {
+        self.col
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
     }
 }
 
@@ -129,5 +309,55 @@ impl Light {
     pub fn new(col: Color) -> Light {
         Light { col }
     }
+
+    /// Emitter with the color of a black body at `temperature` Kelvin and the given luminance in cd/m^2,
+    /// unit radiance of the renderer corresponds to 1 cd/m^2
+    pub fn blackbody(temperature: f32, luminance: f32) -> Light {
+        Light::new(blackbody(temperature) * luminance)
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::{Color, Point3, Vec3};
+    use crate::hittable::HitRecord;
+    use crate::material::{Glass, Material};
+    use crate::ray::Ray;
+    use crate::thin_film::ThinFilm;
+
+    #[test]
+    fn tinted_glass_transmits_its_color_over_the_distance() {
+        let glass = Glass::tinted(1.5, Color::new(0.8, 0.5, 0.0), 2.0);
+        let transmittance = (glass.medium.absorption * -2.0).exp();
+        assert!((transmittance[0] - 0.8).abs() < 1e-5);
+        assert!((transmittance[1] - 0.5).abs() < 1e-5);
+        assert!(transmittance[2] < 1e-5);
+        // Half the distance takes the square root
+        assert!(((glass.medium.absorption * -1.0).exp()[1] - 0.5f32.sqrt()).abs() < 1e-5);
+
+        let degenerate = Glass::tinted(1.5, Color::new(0.0, 1.0, 2.0), 0.0);
+        for i in 0..3 {
+            assert!(degenerate.medium.absorption[i].is_finite() && degenerate.medium.absorption[i] >= 0.0);
+        }
+    }
+
+    #[test]
+    fn coated_glass_reflects_totally_past_the_critical_angle() {
+        let glass = Glass::new(1.5).with_thin_film(ThinFilm::new(300.0, 1.33));
+        let material: Arc<dyn Material> = Arc::new(glass.clone());
+        // Leaving the glass 60 degrees off the normal, past the critical angle of 42 degrees
+        let mut ray = Ray::new(Point3::new(0.0, -1.0, 0.0), Vec3::new(3f32.sqrt(), 1.0, 0.0));
+        ray.media.push(glass.medium);
+        let mut rec = HitRecord::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 1.0, material.clone());
+        rec.set_face_normal(&ray, &Vec3::new(0.0, 1.0, 0.0));
+
+        for _ in 0..100 {
+            let scattered = material.sample(&ray, &rec).unwrap();
+            assert!(scattered.ray.direction[1] < 0.0);
+            for i in 0..3 {
+                assert!((scattered.weight[i] - 1.0).abs() < 1e-5);
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use rand::random;
+use crate::Color;
+
+/// Homogeneous medium filling the interior of a closed dielectric object
+#[derive(Debug, Clone, Copy)]
+pub struct Medium {
+    /// Identifies the object the medium belongs to, so it can be left through any of its surfaces
+    id: usize,
+    /// Refraction index
+    pub ref_idx: f32,
+    /// Absorption coefficient per unit of distance
+    pub absorption: Color,
+    /// Scattering coefficient per unit of distance, zero for clear media
+    pub scattering: Color,
+    /// Where volumes overlap, the medium with the highest priority wins
+    pub priority: u32,
+}
+
+impl Medium {
+    pub fn new(ref_idx: f32, absorption: Color, priority: u32) -> Medium {
+        Medium::participating(ref_idx, absorption, Color::new(0.0, 0.0, 0.0), priority)
+    }
+
+    /// Medium which scatters light as well as absorbs it, like milk, skin or marble
+    pub fn participating(ref_idx: f32, absorption: Color, scattering: Color, priority: u32) -> Medium {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        Medium { id: NEXT_ID.fetch_add(1, Ordering::Relaxed), ref_idx, absorption, scattering, priority }
+    }
+
+    fn is_scattering(&self) -> bool {
+        self.scattering[0] > 0.0 || self.scattering[1] > 0.0 || self.scattering[2] > 0.0
+    }
+
+    /// Samples a free-flight distance along a segment of `max_distance` length.
+    /// Returns the distance to the scattering event, if any, and the weight of the sampled segment
+    fn sample_distance(&self, max_distance: f32) -> (Option<f32>, Color) {
+        let extinction = self.absorption + self.scattering;
+
+        // Each color channel has its own extinction, pick one and weight by the average density of all
+        let channel = ((random::<f32>() * 3.0) as usize).min(2);
+        let distance = if extinction[channel] > 0.0 {
+            -(1.0 - random::<f32>()).ln() / extinction[channel]
+        } else {
+            f32::INFINITY
+        };
+
+        let scattered = distance < max_distance;
+        let distance = distance.min(max_distance);
+        let transmittance = (extinction * -distance).exp();
+        let density = if scattered { extinction * transmittance } else { transmittance };
+        let pdf = (density[0] + density[1] + density[2]) / 3.0;
+        if pdf <= 0.0 {
+            return (None, Color::new(0.0, 0.0, 0.0));
+        }
+
+        if scattered {
+            (Some(distance), self.scattering * transmittance * (1.0 / pdf))
+        } else {
+            (None, transmittance * (1.0 / pdf))
+        }
+    }
+}
+
+/// Media a ray is currently inside of, in the order they were entered
+#[derive(Debug, Clone, Default)]
+pub struct MediumStack {
+    imp: Vec<Medium>,
+}
+
+impl MediumStack {
+    pub fn new() -> MediumStack {
+        MediumStack { imp: Vec::new() }
+    }
+
+    /// The medium which actually fills the space around the ray:
+    /// the one with the highest priority, the most recently entered one on ties
+    pub fn current(&self) -> Option<&Medium> {
+        // `max_by_key` keeps the last of equal elements, which is the innermost one
+        self.imp.iter().max_by_key(|m| m.priority)
+    }
+
+    /// Refraction index around the ray, vacuum outside of any medium
+    pub fn ref_idx(&self) -> f32 {
+        self.current().map_or(1.0, |m| m.ref_idx)
+    }
+
+    /// Follows the ray through the current medium for `distance` until it reaches a surface.
+    /// Returns the distance where the ray is scattered by the medium instead, if it is,
+    /// and the fraction of light left over the travelled segment
+    pub fn sample(&self, distance: f32) -> (Option<f32>, Color) {
+        match self.current() {
+            Some(m) if m.is_scattering() => m.sample_distance(distance),
+            Some(m) => (None, (m.absorption * -distance).exp()),
+            None => (None, Color::new(1.0, 1.0, 1.0)),
+        }
+    }
+
+    /// Fraction of light passing through `distance` of the current medium without being absorbed or scattered
+    pub fn transmittance(&self, distance: f32) -> Color {
+        match self.current() {
+            Some(m) => ((m.absorption + m.scattering) * -distance).exp(),
+            None => Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn push(&mut self, medium: Medium) {
+        self.imp.push(medium);
+    }
+
+    pub fn remove(&mut self, medium: &Medium) {
+        if let Some(pos) = self.imp.iter().rposition(|m| m.id == medium.id) {
+            self.imp.remove(pos);
+        }
+    }
+
+    /// Media on the other side of the surface enclosing `medium`
+    pub fn crossing(&self, medium: &Medium, entering: bool) -> MediumStack {
+        let mut media = self.clone();
+        if entering {
+            media.push(*medium);
+        } else {
+            media.remove(medium);
+        }
+        media
+    }
+
+    /// Whether the medium around the ray stays the same after crossing into `other`
+    pub fn same_current(&self, other: &MediumStack) -> bool {
+        self.current().map(|m| m.id) == other.current().map(|m| m.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Color;
+    use crate::medium::{Medium, MediumStack};
+
+    #[test]
+    fn priority_resolves_overlapping_media() {
+        let clear = Color::new(0.0, 0.0, 0.0);
+        let water = Medium::new(1.33, clear, 0);
+        let glass = Medium::new(1.5, clear, 1);
+
+        let mut media = MediumStack::new();
+        assert_eq!(media.ref_idx(), 1.0);
+
+        // Entering glass, then the water it contains through the overlapping wall
+        media.push(glass);
+        let before = media.clone();
+        media.push(water);
+        assert!(before.same_current(&media));
+        assert_eq!(media.ref_idx(), 1.5);
+
+        // Leaving glass wall into the water
+        media.remove(&glass);
+        assert_eq!(media.ref_idx(), 1.33);
+
+        media.remove(&water);
+        assert_eq!(media.ref_idx(), 1.0);
+    }
+
+    #[test]
+    fn innermost_medium_wins_on_equal_priority() {
+        let clear = Color::new(0.0, 0.0, 0.0);
+        let water = Medium::new(1.33, clear, 0);
+        let ice = Medium::new(1.31, clear, 0);
+
+        // Ice floating in water is a real interface to refract at
+        let mut media = MediumStack::new();
+        media.push(water);
+        let outside = media.clone();
+        media.push(ice);
+        assert!(!outside.same_current(&media));
+        assert_eq!(media.ref_idx(), 1.31);
+
+        media.remove(&ice);
+        assert_eq!(media.ref_idx(), 1.33);
+    }
+}
@@ -0,0 +1,150 @@
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use crate::{Color, Vec3};
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::onb::Onb;
+use crate::ray::Ray;
+
+const THETA_H_RES: usize = 90;
+const THETA_D_RES: usize = 90;
+const PHI_D_RES: usize = 180;
+const CHANNEL_SIZE: usize = THETA_H_RES * THETA_D_RES * PHI_D_RES;
+
+/// Scales converting stored values of red, green and blue channels into reflectance
+const CHANNEL_SCALE: [f32; 3] = [1.0 / 1500.0, 1.15 / 1500.0, 1.66 / 1500.0];
+
+/// Measured isotropic BRDF in the MERL database binary format (Matusik et al. 2003)
+#[derive(Debug, Clone)]
+pub struct Merl {
+    /// Tabulated BRDF over half and difference angles, channels stored one after another
+    data: Vec<f32>,
+}
+
+impl Merl {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Merl> {
+        Merl::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> io::Result<Merl> {
+        let mut dims = [0usize; 3];
+        let mut buf = [0u8; 4];
+        for dim in dims.iter_mut() {
+            reader.read_exact(&mut buf)?;
+            *dim = i32::from_le_bytes(buf) as usize;
+        }
+        if dims != [THETA_H_RES, THETA_D_RES, PHI_D_RES] {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("unexpected MERL table dimensions {:?}", dims)));
+        }
+
+        let mut data = Vec::with_capacity(3 * CHANNEL_SIZE);
+        let mut buf = [0u8; 8];
+        for i in 0..3 * CHANNEL_SIZE {
+            reader.read_exact(&mut buf)?;
+            // Negative values mark directions which were not measured
+            let value = f64::from_le_bytes(buf).max(0.0) as f32;
+            data.push(value * CHANNEL_SCALE[i / CHANNEL_SIZE]);
+        }
+
+        Ok(Merl { data })
+    }
+
+    /// Table index for the half vector angle, sampled more densely near the specular peak
+    fn theta_h_index(theta_h: f32) -> usize {
+        let idx = ((theta_h / (PI / 2.0)).max(0.0).sqrt() * THETA_H_RES as f32) as usize;
+        idx.min(THETA_H_RES - 1)
+    }
+
+    fn theta_d_index(theta_d: f32) -> usize {
+        let idx = (theta_d / (PI / 2.0) * THETA_D_RES as f32) as usize;
+        idx.min(THETA_D_RES - 1)
+    }
+
+    fn phi_d_index(phi_d: f32) -> usize {
+        // Reciprocity lets the table store only half of the range
+        let phi_d = if phi_d < 0.0 { phi_d + PI } else { phi_d };
+        let idx = (phi_d / PI * PHI_D_RES as f32) as usize;
+        idx.min(PHI_D_RES - 1)
+    }
+
+    /// BRDF for directions given in the local frame with normal along z
+    pub fn eval_local(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        if wo[2] <= 0.0 || wi[2] <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let h = (*wo + *wi).unit_vector();
+        let theta_h = h[2].clamp(-1.0, 1.0).acos();
+        let phi_h = h[1].atan2(h[0]);
+
+        // Rotate `wi` so that the half vector becomes the normal
+        let (sin_phi, cos_phi) = (-phi_h).sin_cos();
+        let d = Vec3::new(wi[0] * cos_phi - wi[1] * sin_phi, wi[0] * sin_phi + wi[1] * cos_phi, wi[2]);
+        let (sin_theta, cos_theta) = (-theta_h).sin_cos();
+        let d = Vec3::new(d[0] * cos_theta + d[2] * sin_theta, d[1], -d[0] * sin_theta + d[2] * cos_theta);
+
+        let theta_d = d[2].clamp(-1.0, 1.0).acos();
+        let phi_d = d[1].atan2(d[0]);
+
+        let idx = Merl::phi_d_index(phi_d)
+            + Merl::theta_d_index(theta_d) * PHI_D_RES
+            + Merl::theta_h_index(theta_h) * PHI_D_RES * THETA_D_RES;
+        Color::new(self.data[idx], self.data[idx + CHANNEL_SIZE], self.data[idx + 2 * CHANNEL_SIZE])
+    }
+}
+
+impl Material for Merl {
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        let uvw = Onb::from_w(&hit_rec.normal);
+        let wo = uvw.to_local(&-r_in.direction.unit_vector());
+        let wi = Vec3::rand_cosine_direction();
+
+        // Cosine-weighted sampling cancels the cosine term, leaving BRDF over its density
+        let weight = self.eval_local(&wo, &wi) * PI;
+        Some(ScatterRecord::new(r_in.spawn(hit_rec.p, uvw.local(&wi)), weight, wi[2] / PI))
+    }
+
+    fn eval(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> Color {
+        let uvw = Onb::from_w(&hit_rec.normal);
+        let wo = uvw.to_local(&-r_in.direction.unit_vector());
+        let wi = uvw.to_local(direction);
+        self.eval_local(&wo, &wi) * wi[2].max(0.0)
+    }
+
+    fn pdf(&self, _r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> f32 {
+        direction.dot(hit_rec.normal).max(0.0) / PI
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::merl::{CHANNEL_SIZE, Merl};
+    use crate::Vec3;
+
+    #[test]
+    fn reads_channels_with_scales() {
+        let mut bytes = Vec::new();
+        for dim in [90i32, 90, 180] {
+            bytes.extend_from_slice(&dim.to_le_bytes());
+        }
+        for value in [1500.0f64, 1500.0, -1.0] {
+            for _ in 0..CHANNEL_SIZE {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        let merl = Merl::from_reader(bytes.as_slice()).unwrap();
+        let wo = Vec3::new(0.3, 0.1, 0.9).unit_vector();
+        let wi = Vec3::new(-0.5, 0.2, 0.7).unit_vector();
+        let value = merl.eval_local(&wo, &wi);
+        assert!((value[0] - 1.0).abs() < 1e-6);
+        assert!((value[1] - 1.15).abs() < 1e-6);
+        assert_eq!(value[2], 0.0);
+
+        assert!(Merl::from_reader(&bytes[..100]).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use std::f32::consts::PI;
+use crate::{Color, Vec3};
+
+/// GGX (Trowbridge–Reitz) normal distribution, `cos_h` is the cosine between the normal and half vector
+pub fn ggx_d(cos_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let t = cos_h * cos_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * t * t)
+}
+
+/// Smith masking term for GGX
+pub fn smith_g1(cos_v: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let cos_v = cos_v.abs();
+    2.0 * cos_v / (cos_v + (a2 + (1.0 - a2) * cos_v * cos_v).sqrt())
+}
+
+/// Samples a half vector in local coordinates (normal along z) proportionally to `ggx_d(cos_h) * cos_h`
+pub fn sample_ggx(alpha: f32, u1: f32, u2: f32) -> Vec3 {
+    let phi = 2.0 * PI * u1;
+    let cos_theta = ((1.0 - u2) / (1.0 + (alpha * alpha - 1.0) * u2)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+/// Generalized Trowbridge–Reitz with gamma = 1, used by Burley for the clearcoat lobe
+pub fn gtr1_d(cos_h: f32, alpha: f32) -> f32 {
+    if alpha >= 1.0 {
+        return 1.0 / PI;
+    }
+    let a2 = alpha * alpha;
+    (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * cos_h * cos_h))
+}
+
+/// Samples a half vector in local coordinates proportionally to `gtr1_d(cos_h) * cos_h`
+pub fn sample_gtr1(alpha: f32, u1: f32, u2: f32) -> Vec3 {
+    let a2 = alpha * alpha;
+    let phi = 2.0 * PI * u1;
+    let cos_theta = ((1.0 - a2.powf(1.0 - u2)) / (1.0 - a2)).max(0.0).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+/// Schlick weight `(1 - cos)^5`
+pub fn schlick_weight(cos: f32) -> f32 {
+    (1.0 - cos).clamp(0.0, 1.0).powi(5)
+}
+
+/// Schlick approximation of the Fresnel reflectance for the given normal incidence reflectance
+pub fn schlick_fresnel(f0: Color, cos: f32) -> Color {
+    let w = schlick_weight(cos);
+    f0 * (1.0 - w) + Color::new(w, w, w)
+}
+
+/// Unpolarized Fresnel reflectance of a dielectric interface, `eta` is etai_over_etat.
+/// Returns 1 on total internal reflection
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let cos_i = cos_i.abs().min(1.0);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_s = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_p = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (r_s * r_s + r_p * r_p)
+}
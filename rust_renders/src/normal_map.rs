@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use crate::{Color, Vec3};
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::texture::Texture;
+
+/// Step in surface coordinates used to differentiate bump maps
+const BUMP_DELTA: f32 = 0.0005;
+
+#[derive(Debug, Clone)]
+enum Perturbation {
+    /// Tangent-space normal map, with tangent along increasing `u` and bitangent along increasing `v`
+    NormalMap(Arc<dyn Texture>),
+    /// Height field, displacement along the normal is texture luminance times the scale
+    Bump(Arc<dyn Texture>, f32),
+}
+
+/// Adds surface detail to any material by perturbing its shading normal.
+/// The geometric normal is kept to spawn rays and to discard directions which would go through the surface
+#[derive(Debug, Clone)]
+pub struct NormalMapped {
+    base: Arc<dyn Material + Send + Sync>,
+    perturbation: Perturbation,
+}
+
+impl NormalMapped {
+    pub fn normal_map(base: Arc<dyn Material + Send + Sync>, map: Arc<dyn Texture>) -> NormalMapped {
+        NormalMapped { base, perturbation: Perturbation::NormalMap(map) }
+    }
+
+    pub fn bump_map(base: Arc<dyn Material + Send + Sync>, height: Arc<dyn Texture>, scale: f32) -> NormalMapped {
+        NormalMapped { base, perturbation: Perturbation::Bump(height, scale) }
+    }
+
+    /// Perturbed normal facing the same side as the record's one
+    fn shading_normal(&self, hit_rec: &HitRecord) -> Vec3 {
+        let normal = self.outward_shading_normal(hit_rec);
+        if hit_rec.front_face { normal } else { -normal }
+    }
+
+    fn perturbed(&self, r_in: &Ray, hit_rec: &HitRecord) -> HitRecord {
+        let mut perturbed = hit_rec.clone();
+        let normal = self.shading_normal(hit_rec);
+        // Viewing the perturbed surface from behind is not possible, fall back to the actual normal then
+        if normal.dot(r_in.direction) < 0.0 {
+            perturbed.normal = normal;
+        }
+        perturbed
+    }
+
+    fn outward_shading_normal(&self, hit_rec: &HitRecord) -> Vec3 {
+        let n = if hit_rec.front_face { hit_rec.normal } else { -hit_rec.normal };
+        match &self.perturbation {
+            Perturbation::NormalMap(map) => {
+                let tangent = hit_rec.dpdu - n * n.dot(hit_rec.dpdu);
+                if tangent.length_squared() <= 0.0 {
+                    return n;
+                }
+                let tangent = tangent.unit_vector();
+                let bitangent = n.cross(&tangent);
+
+                let c = map.value(hit_rec);
+                let local = Vec3::new(2.0 * c[0] - 1.0, 2.0 * c[1] - 1.0, 2.0 * c[2] - 1.0);
+                (tangent * local[0] + bitangent * local[1] + n * local[2]).unit_vector()
+            }
+            Perturbation::Bump(height, scale) => {
+                let displacement = |rec: &HitRecord| height.value(rec).luminance() * scale;
+                let d = displacement(hit_rec);
+
+                let mut shifted = hit_rec.clone();
+                shifted.u += BUMP_DELTA;
+                shifted.p = hit_rec.p + hit_rec.dpdu * BUMP_DELTA;
+                let du = (displacement(&shifted) - d) / BUMP_DELTA;
+
+                let mut shifted = hit_rec.clone();
+                shifted.v += BUMP_DELTA;
+                shifted.p = hit_rec.p + hit_rec.dpdv * BUMP_DELTA;
+                let dv = (displacement(&shifted) - d) / BUMP_DELTA;
+
+                let dpdu = hit_rec.dpdu + n * du;
+                let dpdv = hit_rec.dpdv + n * dv;
+                let bumped = dpdu.cross(&dpdv);
+                if bumped.length_squared() <= 0.0 {
+                    return n;
+                }
+                let bumped = bumped.unit_vector();
+                if bumped.dot(n) < 0.0 { -bumped } else { bumped }
+            }
+        }
+    }
+}
+
+impl Material for NormalMapped {
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        let perturbed = self.perturbed(r_in, hit_rec);
+        let mut record = self.base.sample(r_in, &perturbed)?;
+
+        // The material decides between reflection and transmission by the shading normal,
+        // the direction has to agree with the actual surface or light would leak through it
+        let shading_side = record.ray.direction.dot(perturbed.normal) > 0.0;
+        let geometric_side = record.ray.direction.dot(hit_rec.geometric_normal) > 0.0;
+        if shading_side != geometric_side {
+            return None;
+        }
+
+        record.ray.origin = hit_rec.offset_origin(&record.ray.direction);
+        Some(record)
+    }
+
+    fn opacity(&self, hit_rec: &HitRecord) -> f32 {
+        self.base.opacity(hit_rec)
+    }
+
+    fn emitted(&self, r_in: &Ray, hit_rec: &HitRecord) -> Color {
+        self.base.emitted(r_in, hit_rec)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+
+    fn eval(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> Color {
+        // Light leaking through the actual surface, as in sampling
+        let perturbed = self.perturbed(r_in, hit_rec);
+        let shading_side = direction.dot(perturbed.normal) > 0.0;
+        let geometric_side = direction.dot(hit_rec.geometric_normal) > 0.0;
+        if shading_side != geometric_side {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.base.eval(r_in, &perturbed, direction)
+    }
+
+    /// Directions rejected by `sample` keep their density, their samples are just lost
+    fn pdf(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> f32 {
+        self.base.pdf(r_in, &self.perturbed(r_in, hit_rec), direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::{Color, Lambertian, Point3, Vec3};
+    use crate::hittable::HitRecord;
+    use crate::normal_map::NormalMapped;
+    use crate::ray::Ray;
+    use crate::texture::SolidColor;
+
+    #[test]
+    fn flat_maps_keep_the_normal() {
+        let base = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let flat = NormalMapped::normal_map(base.clone(), Arc::new(SolidColor::new(Color::new(0.5, 0.5, 1.0))));
+        let level = NormalMapped::bump_map(base.clone(), Arc::new(SolidColor::new(Color::new(0.3, 0.3, 0.3))), 2.0);
+        let outward = Vec3::new(0.0, 0.6, 0.8);
+
+        // Hit from either side of the surface
+        for &origin in &[Point3::new(0.0, 1.0, 1.0), Point3::new(0.0, -1.0, -1.0)] {
+            let r_in = Ray::new(origin, -origin);
+            let mut hit_rec = HitRecord::new(Point3::new(0.0, 0.0, 0.0), outward, 1.0, base.clone());
+            hit_rec.set_face_normal(&r_in, &outward);
+            hit_rec.dpdu = Vec3::new(1.0, 0.0, 0.0);
+            hit_rec.dpdv = Vec3::new(0.0, -0.8, 0.6);
+
+            for material in &[&flat, &level] {
+                let normal = material.shading_normal(&hit_rec);
+                assert!((normal - hit_rec.normal).length() < 1e-5, "{:?} {:?}", normal, hit_rec.normal);
+            }
+            // Texels leaning towards +u tilt the normal along the tangent
+            let tilted = NormalMapped::normal_map(base.clone(), Arc::new(SolidColor::new(Color::new(1.0, 0.5, 0.5))));
+            let normal = tilted.outward_shading_normal(&hit_rec);
+            assert!((normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-5, "{:?}", normal);
+        }
+    }
+}
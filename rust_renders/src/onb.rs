@@ -0,0 +1,31 @@
+use crate::Vec3;
+
+/// Orthonormal basis with `w` along the given normal
+#[derive(Debug, Clone)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    /// Builds the basis without branching on the normal direction (Duff et al. 2017)
+    pub fn from_w(n: &Vec3) -> Onb {
+        let sign = 1.0f32.copysign(n[2]);
+        let a = -1.0 / (sign + n[2]);
+        let b = n[0] * n[1] * a;
+        let u = Vec3::new(1.0 + sign * n[0] * n[0] * a, sign * b, -sign * n[0]);
+        let v = Vec3::new(b, sign + n[1] * n[1] * a, -n[1]);
+        Onb { u, v, w: *n }
+    }
+
+    /// Transforms local coordinates into world space
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        self.u * a[0] + self.v * a[1] + self.w * a[2]
+    }
+
+    /// Transforms a world space vector into local coordinates
+    pub fn to_local(&self, a: &Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
@@ -0,0 +1,570 @@
+use std::f32::consts::PI;
+use rand::random;
+use crate::{Color, Vec3};
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::medium::{Medium, MediumStack};
+use crate::microfacet::{fresnel_dielectric, ggx_d, gtr1_d, sample_ggx, sample_gtr1, schlick_fresnel, schlick_weight, smith_g1};
+use crate::onb::Onb;
+use crate::ray::Ray;
+
+/// Parameters of the principled BSDF, as in Burley's "Physically Based Shading at Disney"
+#[derive(Debug, Clone)]
+pub struct PrincipledParams {
+    pub base_color: Color,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Dielectric specular level, 0.5 corresponds to 4% reflectance at normal incidence
+    pub specular: f32,
+    pub specular_tint: f32,
+    pub sheen: f32,
+    /// Color of the sheen before `sheen_tint` blends it towards the base color hue, white in Burley's model
+    pub sheen_color: Color,
+    pub sheen_tint: f32,
+    pub clearcoat: f32,
+    pub clearcoat_gloss: f32,
+    pub transmission: f32,
+    /// Refraction index of the transmissive part
+    pub ior: f32,
+}
+
+impl Default for PrincipledParams {
+    fn default() -> Self {
+        PrincipledParams {
+            base_color: Color::new(0.8, 0.8, 0.8),
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_color: Color::new(1.0, 1.0, 1.0),
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            ior: 1.5,
+        }
+    }
+}
+
+/// Material of a glTF 2.0 file, the metallic-roughness core with the transmission, IOR, specular,
+/// clearcoat and sheen extensions. Defaults are the ones of the specification
+#[derive(Debug, Clone)]
+pub struct GltfMaterial {
+    pub base_color_factor: Color,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// `KHR_materials_transmission`
+    pub transmission_factor: f32,
+    /// `KHR_materials_ior`
+    pub ior: f32,
+    /// `KHR_materials_specular`, scales the reflectance of the dielectric part
+    pub specular_factor: f32,
+    /// `KHR_materials_clearcoat`
+    pub clearcoat_factor: f32,
+    pub clearcoat_roughness_factor: f32,
+    /// `KHR_materials_sheen`
+    pub sheen_color_factor: Color,
+}
+
+impl Default for GltfMaterial {
+    fn default() -> Self {
+        GltfMaterial {
+            base_color_factor: Color::new(1.0, 1.0, 1.0),
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            transmission_factor: 0.0,
+            ior: 1.5,
+            specular_factor: 1.0,
+            clearcoat_factor: 0.0,
+            clearcoat_roughness_factor: 0.0,
+            sheen_color_factor: Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Material of a Wavefront MTL file. Defaults are the ones common exporters write
+#[derive(Debug, Clone)]
+pub struct MtlMaterial {
+    /// `Kd`
+    pub diffuse: Color,
+    /// `Ks`
+    pub specular: Color,
+    /// `Ns`, the Phong exponent from 0 to 1000
+    pub shininess: f32,
+    /// `d`, opacity where 1 is fully opaque
+    pub dissolve: f32,
+    /// `Ni`, refraction index
+    pub optical_density: f32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::new(0.5, 0.5, 0.5),
+            shininess: 250.0,
+            dissolve: 1.0,
+            optical_density: 1.45,
+        }
+    }
+}
+
+impl PrincipledParams {
+    /// The brightest channel of the glTF sheen color sets the strength of the sheen, the color
+    /// itself its hue. Sheen and clearcoat roughness have no counterpart, clearcoat gloss follows the latter
+    pub fn from_gltf(material: &GltfMaterial) -> PrincipledParams {
+        let ior = material.ior.max(1.0);
+        let f0 = ((ior - 1.0) / (ior + 1.0)).powi(2);
+        let sheen_color = material.sheen_color_factor;
+        let sheen = sheen_color[0].max(sheen_color[1]).max(sheen_color[2]).clamp(0.0, 1.0);
+        PrincipledParams {
+            base_color: material.base_color_factor,
+            metallic: material.metallic_factor.clamp(0.0, 1.0),
+            roughness: material.roughness_factor.clamp(0.0, 1.0),
+            // Specular 1 is a reflectance of 8% at normal incidence
+            specular: f0 / 0.08 * material.specular_factor.max(0.0),
+            specular_tint: 0.0,
+            sheen,
+            sheen_color: if sheen > 0.0 { sheen_color * (1.0 / sheen) } else { Color::new(1.0, 1.0, 1.0) },
+            sheen_tint: 0.0,
+            clearcoat: material.clearcoat_factor.clamp(0.0, 1.0),
+            clearcoat_gloss: 1.0 - material.clearcoat_roughness_factor.clamp(0.0, 1.0),
+            transmission: material.transmission_factor.clamp(0.0, 1.0),
+            ior,
+        }
+    }
+
+    /// Same conversion as Blender's importer: the Phong exponent becomes roughness, the average
+    /// of `Ks` the specular level and whatever doesn't dissolve is transmitted
+    pub fn from_mtl(material: &MtlMaterial) -> PrincipledParams {
+        let ks = material.specular;
+        PrincipledParams {
+            base_color: material.diffuse,
+            metallic: 0.0,
+            roughness: 1.0 - (material.shininess.clamp(0.0, 1000.0) / 1000.0).sqrt(),
+            specular: ((ks[0] + ks[1] + ks[2]) / 3.0).clamp(0.0, 1.0),
+            transmission: (1.0 - material.dissolve).clamp(0.0, 1.0),
+            // Many exporters write 1 when they don't know the refraction index
+            ior: if material.optical_density > 1.0 { material.optical_density } else { 1.5 },
+            ..PrincipledParams::default()
+        }
+    }
+}
+
+/// Disney principled BSDF: diffuse with sheen, GGX specular, GTR1 clearcoat and rough transmission lobes
+#[derive(Debug, Clone)]
+pub struct Principled {
+    params: PrincipledParams,
+    /// Medium enclosed by the surface, used by the transmission lobe
+    medium: Medium,
+}
+
+impl Principled {
+    pub fn new(params: PrincipledParams) -> Principled {
+        let medium = Medium::new(params.ior, Color::new(0.0, 0.0, 0.0), 0);
+        Principled { params, medium }
+    }
+
+    /// glTF metallic-roughness material, the common target for imported scenes
+    pub fn from_metallic_roughness(base_color: Color, metallic: f32, roughness: f32) -> Principled {
+        Principled::new(PrincipledParams { base_color, metallic, roughness, ..Default::default() })
+    }
+
+    pub fn from_gltf(material: &GltfMaterial) -> Principled {
+        Principled::new(PrincipledParams::from_gltf(material))
+    }
+
+    pub fn from_mtl(material: &MtlMaterial) -> Principled {
+        Principled::new(PrincipledParams::from_mtl(material))
+    }
+
+    fn alpha(&self) -> f32 {
+        (self.params.roughness * self.params.roughness).max(1e-3)
+    }
+
+    fn clearcoat_alpha(&self) -> f32 {
+        0.1 + (0.001 - 0.1) * self.params.clearcoat_gloss
+    }
+
+    /// Base color hue with luminance normalized to one
+    fn tint(&self) -> Color {
+        let lum = self.params.base_color.luminance();
+        if lum > 0.0 { self.params.base_color * (1.0 / lum) } else { Color::new(1.0, 1.0, 1.0) }
+    }
+
+    fn specular_f0(&self) -> Color {
+        let p = &self.params;
+        let tint = Color::new(1.0, 1.0, 1.0) * (1.0 - p.specular_tint) + self.tint() * p.specular_tint;
+        tint * (0.08 * p.specular * (1.0 - p.metallic)) + p.base_color * p.metallic
+    }
+
+    /// Probabilities of sampling the diffuse, specular, clearcoat and transmission lobes
+    fn lobe_probabilities(&self, cos_o: f32) -> [f32; 4] {
+        let p = &self.params;
+        let dielectric = 1.0 - p.metallic;
+        let weights = [
+            dielectric * (1.0 - p.transmission) * p.base_color.luminance(),
+            (1.0 - dielectric * p.transmission) * schlick_fresnel(self.specular_f0(), cos_o).luminance(),
+            0.25 * p.clearcoat * (0.04 + 0.96 * schlick_weight(cos_o)),
+            dielectric * p.transmission,
+        ];
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            return [0.0; 4];
+        }
+        [weights[0] / total, weights[1] / total, weights[2] / total, weights[3] / total]
+    }
+
+    /// BSDF of the reflection lobes, both directions on the side of the normal
+    fn eval_reflection(&self, n: &Vec3, wo: &Vec3, wi: &Vec3) -> Color {
+        let p = &self.params;
+        let cos_o = wo.dot(*n).max(1e-4);
+        let cos_i = wi.dot(*n).max(1e-4);
+        let h = (*wo + *wi).unit_vector();
+        let cos_h = h.dot(*n);
+        let cos_d = wi.dot(h);
+
+        // Diffuse with retro-reflection and sheen
+        let fd90 = 0.5 + 2.0 * p.roughness * cos_d * cos_d;
+        let fd = (1.0 + (fd90 - 1.0) * schlick_weight(cos_i)) * (1.0 + (fd90 - 1.0) * schlick_weight(cos_o));
+        let sheen_color = p.sheen_color * (1.0 - p.sheen_tint) + self.tint() * p.sheen_tint;
+        let diffuse = p.base_color * (fd / PI) + sheen_color * (p.sheen * schlick_weight(cos_d));
+        let diffuse = diffuse * ((1.0 - p.metallic) * (1.0 - p.transmission));
+
+        let alpha = self.alpha();
+        let g = smith_g1(cos_o, alpha) * smith_g1(cos_i, alpha);
+        let specular = schlick_fresnel(self.specular_f0(), cos_d)
+            * (ggx_d(cos_h, alpha) * g / (4.0 * cos_o * cos_i) * (1.0 - (1.0 - p.metallic) * p.transmission));
+
+        let gc = smith_g1(cos_o, 0.25) * smith_g1(cos_i, 0.25);
+        let fc = 0.04 + 0.96 * schlick_weight(cos_d);
+        let clearcoat = 0.25 * p.clearcoat * gtr1_d(cos_h, self.clearcoat_alpha()) * fc * gc / (4.0 * cos_o * cos_i);
+
+        diffuse + specular + Color::new(clearcoat, clearcoat, clearcoat)
+    }
+
+    /// Density of sampling `wi` with the reflection lobes, including lobe selection probabilities
+    fn pdf_reflection(&self, n: &Vec3, wo: &Vec3, wi: &Vec3, probs: &[f32; 4]) -> f32 {
+        let h = (*wo + *wi).unit_vector();
+        let cos_h = h.dot(*n);
+        let jacobian = 1.0 / (4.0 * wo.dot(h).abs());
+
+        probs[0] * wi.dot(*n) / PI
+            + probs[1] * ggx_d(cos_h, self.alpha()) * cos_h * jacobian
+            + probs[2] * gtr1_d(cos_h, self.clearcoat_alpha()) * cos_h * jacobian
+    }
+
+    /// Perfectly smooth interfaces are too narrow to evaluate, so they are sampled as delta lobes
+    fn smooth_transmission(&self) -> bool {
+        self.params.roughness * self.params.roughness <= 1e-3
+    }
+
+    /// Media behind the transmissive surface and the ratio of refraction indices across it,
+    /// `None` when the surface lies inside of a volume with higher priority
+    fn crossing(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<(MediumStack, f32)> {
+        let media = r_in.media.crossing(&self.medium, hit_rec.front_face);
+        if r_in.media.same_current(&media) {
+            return None;
+        }
+        let etai_over_etat = r_in.media.ref_idx() / media.ref_idx();
+        Some((media, etai_over_etat))
+    }
+
+    /// BSDF of the rough dielectric interface (Walter et al. 2007), `wi` on either side of the
+    /// surface. Like `Glass`, radiance is not scaled by the squared ratio of refraction indices
+    fn eval_transmission(&self, n: &Vec3, wo: &Vec3, wi: &Vec3, etai_over_etat: f32) -> Color {
+        let p = &self.params;
+        let weight = (1.0 - p.metallic) * p.transmission;
+        let alpha = self.alpha();
+        let cos_o = wo.dot(*n).max(1e-4);
+        let cos_i = wi.dot(*n);
+        let g = smith_g1(cos_o, alpha) * smith_g1(cos_i.abs(), alpha);
+
+        if cos_i > 0.0 {
+            let h = (*wo + *wi).unit_vector();
+            let f = fresnel_dielectric(wo.dot(h), etai_over_etat);
+            let value = weight * f * ggx_d(h.dot(*n), alpha) * g / (4.0 * cos_o * cos_i.max(1e-4));
+            return Color::new(value, value, value);
+        }
+
+        match refracting_microfacet(n, wo, wi, etai_over_etat) {
+            Some((h, jacobian)) => {
+                let cos_oh = wo.dot(h);
+                let f = fresnel_dielectric(cos_oh, etai_over_etat);
+                let value = (1.0 - f) * ggx_d(h.dot(*n), alpha) * g * jacobian * cos_oh / (cos_o * (-cos_i).max(1e-4));
+                p.base_color * (weight * value)
+            }
+            None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Density of sampling `wi` on the rough dielectric interface, without the lobe selection probability
+    fn pdf_transmission(&self, n: &Vec3, wo: &Vec3, wi: &Vec3, etai_over_etat: f32) -> f32 {
+        let alpha = self.alpha();
+        if wi.dot(*n) > 0.0 {
+            let h = (*wo + *wi).unit_vector();
+            let cos_oh = wo.dot(h);
+            let f = fresnel_dielectric(cos_oh, etai_over_etat);
+            return f * ggx_d(h.dot(*n), alpha) * h.dot(*n) / (4.0 * cos_oh.max(1e-4));
+        }
+
+        match refracting_microfacet(n, wo, wi, etai_over_etat) {
+            Some((h, jacobian)) => {
+                let f = fresnel_dielectric(wo.dot(h), etai_over_etat);
+                (1.0 - f) * ggx_d(h.dot(*n), alpha) * h.dot(*n) * jacobian
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Perfectly smooth dielectric interface, reflecting or refracting about the normal
+    fn sample_smooth_transmission(&self, r_in: &Ray, hit_rec: &HitRecord, media: MediumStack, etai_over_etat: f32) -> ScatterRecord {
+        let n = hit_rec.normal;
+        let unit_direction = r_in.direction.unit_vector();
+
+        if random::<f32>() < fresnel_dielectric((-unit_direction).dot(n), etai_over_etat) {
+            let reflected = Vec3::reflect(&unit_direction, &n);
+            let mut ray = r_in.spawn(hit_rec.p, reflected);
+            ray.differential = hit_rec.reflected_differential(r_in, &reflected);
+            return ScatterRecord::delta(ray, Color::new(1.0, 1.0, 1.0));
+        }
+
+        let refracted = Vec3::refract(&unit_direction, &n, etai_over_etat);
+        let differential = hit_rec.refracted_differential(r_in, &refracted, etai_over_etat);
+        ScatterRecord::delta(Ray { origin: hit_rec.p, direction: refracted, media, differential }, self.params.base_color)
+    }
+}
+
+/// Microfacet normal refracting `wo` into `wi` below the surface, with the Jacobian of the
+/// refraction from the half vector to the direction (Walter et al. 2007)
+fn refracting_microfacet(n: &Vec3, wo: &Vec3, wi: &Vec3, etai_over_etat: f32) -> Option<(Vec3, f32)> {
+    let eta = 1.0 / etai_over_etat;
+    let mut h = (*wo + *wi * eta).unit_vector();
+    if h.dot(*n) < 0.0 {
+        h = -h;
+    }
+    let (cos_oh, cos_ih) = (wo.dot(h), wi.dot(h));
+    if cos_oh <= 0.0 || cos_ih >= 0.0 {
+        return None;
+    }
+    let denom = cos_oh + eta * cos_ih;
+    Some((h, eta * eta * -cos_ih / (denom * denom)))
+}
+
+impl Material for Principled {
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        let n = hit_rec.normal;
+        let wo = -r_in.direction.unit_vector();
+        let cos_o = wo.dot(n).max(1e-4);
+        let uvw = Onb::from_w(&n);
+
+        let crossing = if self.params.transmission > 0.0 {
+            match self.crossing(r_in, hit_rec) {
+                Some(crossing) => Some(crossing),
+                None => {
+                    // Transmissive surface lies inside of a volume with higher priority
+                    let media = r_in.media.crossing(&self.medium, hit_rec.front_face);
+                    let ray = Ray { origin: hit_rec.p, direction: r_in.direction, media, differential: r_in.differential };
+                    return Some(ScatterRecord::delta(ray, Color::new(1.0, 1.0, 1.0)));
+                }
+            }
+        } else {
+            None
+        };
+
+        let probs = self.lobe_probabilities(cos_o);
+        let xi = random::<f32>();
+        let reflection_prob = probs[0] + probs[1] + probs[2];
+        let ray = if xi >= reflection_prob {
+            let (media, etai_over_etat) = match crossing {
+                Some(crossing) if probs[3] > 0.0 => crossing,
+                _ => return None,
+            };
+            if self.smooth_transmission() {
+                // Fresnel is already accounted for by choosing between reflection and refraction
+                let p = &self.params;
+                let mut record = self.sample_smooth_transmission(r_in, hit_rec, media, etai_over_etat);
+                record.weight = record.weight * ((1.0 - p.metallic) * p.transmission / probs[3]);
+                return Some(record);
+            }
+
+            let h = uvw.local(&sample_ggx(self.alpha(), random(), random()));
+            let cos_oh = wo.dot(h);
+            if cos_oh <= 0.0 {
+                return None;
+            }
+            let reflect = random::<f32>() < fresnel_dielectric(cos_oh, etai_over_etat);
+            let direction = if reflect {
+                Vec3::reflect(&-wo, &h)
+            } else {
+                Vec3::refract(&-wo, &h, etai_over_etat)
+            };
+            if (direction.dot(n) > 0.0) != reflect {
+                return None;
+            }
+            if reflect {
+                r_in.spawn(hit_rec.p, direction)
+            } else {
+                Ray { origin: hit_rec.p, direction, media, differential: None }
+            }
+        } else {
+            let direction = if xi < probs[0] {
+                uvw.local(&Vec3::rand_cosine_direction())
+            } else if xi < probs[0] + probs[1] {
+                let h = uvw.local(&sample_ggx(self.alpha(), random(), random()));
+                Vec3::reflect(&-wo, &h)
+            } else {
+                let h = uvw.local(&sample_gtr1(self.clearcoat_alpha(), random(), random()));
+                Vec3::reflect(&-wo, &h)
+            };
+            if direction.dot(n) <= 0.0 {
+                return None;
+            }
+            r_in.spawn(hit_rec.p, direction)
+        };
+
+        let pdf = self.pdf(r_in, hit_rec, &ray.direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let weight = self.eval(r_in, hit_rec, &ray.direction) * (1.0 / pdf);
+        Some(ScatterRecord::new(ray, weight, pdf))
+    }
+
+    /// Smooth transmission is left to sampling
+    fn eval(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> Color {
+        let n = hit_rec.normal;
+        let wo = -r_in.direction.unit_vector();
+        let cos_i = direction.dot(n);
+
+        let mut f = Color::new(0.0, 0.0, 0.0);
+        if self.params.transmission > 0.0 {
+            match self.crossing(r_in, hit_rec) {
+                Some((_, etai_over_etat)) if !self.smooth_transmission() => {
+                    f = self.eval_transmission(&n, &wo, direction, etai_over_etat);
+                }
+                Some(_) => {}
+                None => return f,
+            }
+        }
+        if cos_i > 0.0 {
+            f += self.eval_reflection(&n, &wo, direction);
+        }
+        f * cos_i.abs()
+    }
+
+    fn pdf(&self, r_in: &Ray, hit_rec: &HitRecord, direction: &Vec3) -> f32 {
+        let n = hit_rec.normal;
+        let wo = -r_in.direction.unit_vector();
+        let probs = self.lobe_probabilities(wo.dot(n).max(1e-4));
+
+        let mut pdf = 0.0;
+        if self.params.transmission > 0.0 {
+            match self.crossing(r_in, hit_rec) {
+                Some((_, etai_over_etat)) if !self.smooth_transmission() => {
+                    pdf = probs[3] * self.pdf_transmission(&n, &wo, direction, etai_over_etat);
+                }
+                Some(_) => {}
+                None => return 0.0,
+            }
+        }
+        if direction.dot(n) > 0.0 {
+            pdf += self.pdf_reflection(&n, &wo, direction, &probs);
+        }
+        pdf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::{Color, Vec3};
+    use crate::furnace::{furnace, integrate};
+    use crate::principled::{GltfMaterial, MtlMaterial, Principled, PrincipledParams};
+
+    #[test]
+    fn partial_transmission_conserves_energy() {
+        let params = |transmission| PrincipledParams {
+            base_color: Color::new(1.0, 1.0, 1.0),
+            roughness: 0.3,
+            transmission,
+            ..PrincipledParams::default()
+        };
+        let wo = Vec3::new(-0.2, 1.0, 0.0).unit_vector();
+        let (_, full) = furnace(Arc::new(Principled::new(params(1.0))), wo);
+        let (reflected, half) = furnace(Arc::new(Principled::new(params(0.5))), wo);
+        let (full, reflected, half) = (full.luminance(), reflected.luminance(), half.luminance());
+        assert!(full > 0.8 && full <= 1.0);
+        assert!((half - 0.5 * full).abs() < 0.03);
+        // Burley's diffuse is not strictly energy conserving, its retro-reflection adds a little
+        assert!(reflected + half <= 1.05);
+    }
+
+    #[test]
+    fn rough_transmission_samples_agree_with_its_pdf() {
+        let principled = Arc::new(Principled::new(PrincipledParams {
+            base_color: Color::new(1.0, 1.0, 1.0),
+            roughness: 0.5,
+            transmission: 1.0,
+            ..PrincipledParams::default()
+        }));
+        for &wo in &[Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.5, 0.0).unit_vector()] {
+            let (reflected, transmitted) = furnace(principled.clone(), wo);
+            let (reflected_eval, transmitted_eval) = integrate(principled.clone(), wo);
+            let (reflected, transmitted) = (reflected.luminance(), transmitted.luminance());
+            let (reflected_eval, transmitted_eval) = (reflected_eval.luminance(), transmitted_eval.luminance());
+            assert!(transmitted > 0.5);
+            assert!((reflected - reflected_eval).abs() < 0.01, "{} {}", reflected, reflected_eval);
+            assert!((transmitted - transmitted_eval).abs() < 0.01, "{} {}", transmitted, transmitted_eval);
+        }
+    }
+
+    #[test]
+    fn imported_materials_map_onto_lobes() {
+        // Default glTF material is a rough white metal
+        let metal = Principled::from_gltf(&GltfMaterial::default());
+        assert_eq!(metal.params.roughness, 1.0);
+        assert!((metal.params.specular - 0.5).abs() < 1e-6);
+        let probs = metal.lobe_probabilities(1.0);
+        assert_eq!((probs[0], probs[2], probs[3]), (0.0, 0.0, 0.0));
+        assert_eq!(probs[1], 1.0);
+
+        let glass = Principled::from_gltf(&GltfMaterial {
+            metallic_factor: 0.0,
+            roughness_factor: 0.1,
+            transmission_factor: 1.0,
+            ior: 1.33,
+            clearcoat_factor: 0.5,
+            clearcoat_roughness_factor: 0.2,
+            sheen_color_factor: Color::new(0.2, 0.4, 0.1),
+            ..GltfMaterial::default()
+        });
+        assert_eq!(glass.params.ior, 1.33);
+        assert!((glass.params.clearcoat_gloss - 0.8).abs() < 1e-6);
+        assert!((glass.params.sheen - 0.4).abs() < 1e-6);
+        let sheen_color = glass.params.sheen_color;
+        assert!((sheen_color[0] - 0.5).abs() + (sheen_color[1] - 1.0).abs() + (sheen_color[2] - 0.25).abs() < 1e-6);
+        // Water reflects 2% at normal incidence
+        assert!((glass.specular_f0()[0] - 0.02).abs() < 1e-3);
+        let probs = glass.lobe_probabilities(1.0);
+        assert_eq!(probs[0], 0.0);
+        assert!(probs[3] > 0.8);
+
+        let plastic = PrincipledParams::from_mtl(&MtlMaterial {
+            diffuse: Color::new(0.6, 0.1, 0.1),
+            specular: Color::new(0.2, 0.3, 0.4),
+            shininess: 250.0,
+            dissolve: 0.75,
+            optical_density: 1.0,
+        });
+        assert!((plastic.roughness - 0.5).abs() < 1e-6);
+        assert!((plastic.specular - 0.3).abs() < 1e-6);
+        assert!((plastic.transmission - 0.25).abs() < 1e-6);
+        assert_eq!(plastic.ior, 1.5);
+        assert_eq!(plastic.metallic, 0.0);
+        let probs = Principled::new(plastic).lobe_probabilities(1.0);
+        assert!(probs[0] > probs[1] && probs[3] > 0.0);
+        assert!((probs.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    }
+}
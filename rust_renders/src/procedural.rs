@@ -0,0 +1,347 @@
+use crate::{Color, Point3, Vec3};
+use crate::hittable::HitRecord;
+use crate::texture::Texture;
+
+const POINT_COUNT: usize = 256;
+
+/// Deterministic pseudo-random value in [0, 1) for a seed, a cell and a coordinate index (splitmix64).
+/// Unlike a seeded generator from `rand`, it stays the same across versions of the crate
+fn hash(seed: u64, i: i64, j: i64, k: i64, axis: u64) -> f32 {
+    let mut h = seed
+        ^ (i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (j as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (k as u64).wrapping_mul(0x1656_67B1_9E37_79F9)
+        ^ axis.wrapping_mul(0x27D4_EB2F_1656_67C5);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^= h >> 31;
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Perlin gradient noise with tables generated from a seed, so renders are reproducible
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let gradients = (0..POINT_COUNT).map(|n| {
+            let z = 2.0 * hash(seed, n as i64, 0, 0, 0) - 1.0;
+            let a = 2.0 * std::f32::consts::PI * hash(seed, n as i64, 0, 0, 1);
+            let r = (1.0 - z * z).sqrt();
+            Vec3::new(r * a.cos(), r * a.sin(), z)
+        }).collect();
+
+        // Fisher-Yates shuffle
+        let permutation = |axis: u64| {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            for n in (1..POINT_COUNT).rev() {
+                let m = ((hash(seed, n as i64, 0, 0, axis) * (n + 1) as f32) as usize).min(n);
+                p.swap(n, m);
+            }
+            p
+        };
+        let perm_x = permutation(2);
+        let perm_y = permutation(3);
+        let perm_z = permutation(4);
+        Perlin { gradients, perm_x, perm_y, perm_z }
+    }
+
+    /// Gradient noise, roughly in [-1, 1]
+    pub fn noise(&self, p: &Point3) -> f32 {
+        let (fx, fy, fz) = (p[0].floor(), p[1].floor(), p[2].floor());
+        let (u, v, w) = (p[0] - fx, p[1] - fy, p[2] - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        // Hermite smoothing hides the grid
+        let (uu, vv, ww) = (u * u * (3.0 - 2.0 * u), v * v * (3.0 - 2.0 * v), w * w * (3.0 - 2.0 * w));
+        let wrap = |c: i64| (c & (POINT_COUNT as i64 - 1)) as usize;
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.gradients[self.perm_x[wrap(i + di)]
+                        ^ self.perm_y[wrap(j + dj)]
+                        ^ self.perm_z[wrap(k + dk)]];
+                    let (di, dj, dk) = (di as f32, dj as f32, dk as f32);
+                    let offset = Vec3::new(u - di, v - dj, w - dk);
+                    accum += (di * uu + (1.0 - di) * (1.0 - uu))
+                        * (dj * vv + (1.0 - dj) * (1.0 - vv))
+                        * (dk * ww + (1.0 - dk) * (1.0 - ww))
+                        * gradient.dot(offset);
+                }
+            }
+        }
+        accum
+    }
+
+    /// Fractal Brownian motion: octaves of noise with growing frequency and decreasing amplitude,
+    /// normalized to the range of a single octave
+    pub fn fbm(&self, p: &Point3, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        let mut sum = 0.0;
+        let mut norm = 0.0;
+        let mut amplitude = 1.0;
+        let mut p = *p;
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * self.noise(&p);
+            norm += amplitude;
+            amplitude *= gain;
+            p *= lacunarity;
+        }
+        sum / norm
+    }
+
+    /// Sum of absolute noise octaves, which has sharp creases
+    pub fn turbulence(&self, p: &Point3, octaves: u32) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut p = *p;
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * self.noise(&p).abs();
+            amplitude *= 0.5;
+            p *= 2.0;
+        }
+        sum
+    }
+}
+
+/// Cellular noise of Worley 1996 with one feature point per unit cell
+#[derive(Debug, Clone)]
+pub struct Worley {
+    seed: u64,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Worley {
+        Worley { seed }
+    }
+
+    /// Distances to the nearest and the second nearest feature points
+    pub fn distances(&self, p: &Point3) -> (f32, f32) {
+        let (ci, cj, ck) = (p[0].floor() as i64, p[1].floor() as i64, p[2].floor() as i64);
+        let mut f1 = f32::INFINITY;
+        let mut f2 = f32::INFINITY;
+        for i in ci - 1..=ci + 1 {
+            for j in cj - 1..=cj + 1 {
+                for k in ck - 1..=ck + 1 {
+                    let feature = Vec3::new(i as f32 + hash(self.seed, i, j, k, 0),
+                                            j as f32 + hash(self.seed, i, j, k, 1),
+                                            k as f32 + hash(self.seed, i, j, k, 2));
+                    let d = (feature - *p).length();
+                    if d < f1 {
+                        f2 = f1;
+                        f1 = d;
+                    } else if d < f2 {
+                        f2 = d;
+                    }
+                }
+            }
+        }
+        (f1, f2)
+    }
+}
+
+/// Maps scalar pattern values onto colors, linearly interpolating between stops
+#[derive(Debug, Clone)]
+pub struct ColorRamp {
+    stops: Vec<(f32, Color)>,
+}
+
+impl ColorRamp {
+    pub fn new(mut stops: Vec<(f32, Color)>) -> ColorRamp {
+        assert!(!stops.is_empty(), "color ramp needs at least one stop");
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        ColorRamp { stops }
+    }
+
+    /// From black at 0 to white at 1, so the pattern can drive scalar parameters
+    pub fn grayscale() -> ColorRamp {
+        ColorRamp::linear(Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn linear(from: Color, to: Color) -> ColorRamp {
+        ColorRamp::new(vec![(0.0, from), (1.0, to)])
+    }
+
+    pub fn value(&self, t: f32) -> Color {
+        let first = self.stops[0];
+        if t <= first.0 {
+            return first.1;
+        }
+        for pair in self.stops.windows(2) {
+            let (t0, c0) = pair[0];
+            let (t1, c1) = pair[1];
+            if t <= t1 {
+                let s = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1.0 };
+                return c0 * (1.0 - s) + c1 * s;
+            }
+        }
+        self.stops[self.stops.len() - 1].1
+    }
+}
+
+/// Fractal noise, either smooth fBm or turbulence
+#[derive(Debug, Clone)]
+pub struct NoiseTexture {
+    noise: Perlin,
+    /// Frequency of the first octave
+    scale: f32,
+    octaves: u32,
+    turbulent: bool,
+    ramp: ColorRamp,
+}
+
+impl NoiseTexture {
+    pub fn fbm(seed: u64, scale: f32, octaves: u32, ramp: ColorRamp) -> NoiseTexture {
+        NoiseTexture { noise: Perlin::new(seed), scale, octaves, turbulent: false, ramp }
+    }
+
+    pub fn turbulence(seed: u64, scale: f32, octaves: u32, ramp: ColorRamp) -> NoiseTexture {
+        NoiseTexture { noise: Perlin::new(seed), scale, octaves, turbulent: true, ramp }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, hit_rec: &HitRecord) -> Color {
+        let p = hit_rec.p * self.scale;
+        let t = if self.turbulent {
+            self.noise.turbulence(&p, self.octaves)
+        } else {
+            0.5 + 0.5 * self.noise.fbm(&p, self.octaves, 2.0, 0.5)
+        };
+        self.ramp.value(t)
+    }
+}
+
+/// Marble veins: sine bands along x distorted by turbulence
+#[derive(Debug, Clone)]
+pub struct Marble {
+    noise: Perlin,
+    /// Frequency of the bands
+    scale: f32,
+    /// Amount of turbulence bending the bands
+    distortion: f32,
+    octaves: u32,
+    ramp: ColorRamp,
+}
+
+impl Marble {
+    pub fn new(seed: u64, scale: f32, distortion: f32, octaves: u32, ramp: ColorRamp) -> Marble {
+        Marble { noise: Perlin::new(seed), scale, distortion, octaves, ramp }
+    }
+}
+
+impl Texture for Marble {
+    fn value(&self, hit_rec: &HitRecord) -> Color {
+        let p = hit_rec.p * self.scale;
+        let t = 0.5 + 0.5 * (p[0] + self.distortion * self.noise.turbulence(&p, self.octaves)).sin();
+        self.ramp.value(t)
+    }
+}
+
+/// Growth rings around the y axis, with grain noise disturbing them
+#[derive(Debug, Clone)]
+pub struct Wood {
+    noise: Perlin,
+    /// Rings per unit of distance from the axis
+    rings: f32,
+    /// Amount of noise displacing the rings
+    distortion: f32,
+    ramp: ColorRamp,
+}
+
+impl Wood {
+    pub fn new(seed: u64, rings: f32, distortion: f32, ramp: ColorRamp) -> Wood {
+        Wood { noise: Perlin::new(seed), rings, distortion, ramp }
+    }
+}
+
+impl Texture for Wood {
+    fn value(&self, hit_rec: &HitRecord) -> Color {
+        let p = hit_rec.p;
+        let radius = (p[0] * p[0] + p[2] * p[2]).sqrt();
+        // Grain is stretched along the trunk
+        let grain = Vec3::new(p[0] * self.rings, p[1] * 0.1 * self.rings, p[2] * self.rings);
+        let ring = radius * self.rings + self.distortion * self.noise.fbm(&grain, 4, 2.0, 0.5);
+        self.ramp.value(ring - ring.floor())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoronoiMode {
+    /// Distance to the nearest feature point, shading each cell from its center
+    Cells,
+    /// Difference between the two nearest distances, dark along cell borders
+    Edges,
+}
+
+/// Worley cellular pattern, like cracked earth, scales or stone tiles
+#[derive(Debug, Clone)]
+pub struct Voronoi {
+    worley: Worley,
+    /// Cells per unit of distance
+    scale: f32,
+    mode: VoronoiMode,
+    ramp: ColorRamp,
+}
+
+impl Voronoi {
+    pub fn new(seed: u64, scale: f32, mode: VoronoiMode, ramp: ColorRamp) -> Voronoi {
+        Voronoi { worley: Worley::new(seed), scale, mode, ramp }
+    }
+}
+
+impl Texture for Voronoi {
+    fn value(&self, hit_rec: &HitRecord) -> Color {
+        let (f1, f2) = self.worley.distances(&(hit_rec.p * self.scale));
+        let t = match self.mode {
+            VoronoiMode::Cells => f1,
+            VoronoiMode::Edges => f2 - f1,
+        };
+        self.ramp.value(t)
+    }
+}
+
+/// Linear gradient between two points in space
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    from: Point3,
+    to: Point3,
+    ramp: ColorRamp,
+}
+
+impl Gradient {
+    pub fn new(from: Point3, to: Point3, ramp: ColorRamp) -> Gradient {
+        Gradient { from, to, ramp }
+    }
+}
+
+impl Texture for Gradient {
+    fn value(&self, hit_rec: &HitRecord) -> Color {
+        let axis = self.to - self.from;
+        let t = (hit_rec.p - self.from).dot(axis) / axis.length_squared();
+        self.ramp.value(t.clamp(0.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Point3;
+    use crate::procedural::{Perlin, Worley};
+
+    #[test]
+    fn noise_is_deterministic_per_seed() {
+        let p = Point3::new(1.3, -2.7, 0.45);
+        assert_eq!(Perlin::new(7).fbm(&p, 5, 2.0, 0.5), Perlin::new(7).fbm(&p, 5, 2.0, 0.5));
+        assert_ne!(Perlin::new(7).noise(&p), Perlin::new(8).noise(&p));
+
+        let (f1, f2) = Worley::new(3).distances(&p);
+        assert_eq!((f1, f2), Worley::new(3).distances(&p));
+        assert!(f1 <= f2);
+    }
+}
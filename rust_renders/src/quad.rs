@@ -0,0 +1,136 @@
+use std::sync::Arc;
+use rand::random;
+use crate::{Point3, Vec3};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+
+/// Parallelogram spanned by edges `u` and `v` from corner `q`
+#[derive(Debug, Clone)]
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    material: Arc<dyn Material + Send + Sync>,
+    normal: Vec3,
+    /// Plane equation constant, `normal.dot(p) == d` for points on the quad
+    d: f32,
+    /// Converts points on the plane into coordinates along the edges
+    w: Vec3,
+    area: f32,
+}
+
+impl Quad {
+    pub fn new(q: Point3,
+               u: Vec3,
+               v: Vec3,
+               material: Arc<dyn Material + Send + Sync>)
+               -> Quad
+    {
+        let n = u.cross(&v);
+        let normal = n.unit_vector();
+        let d = normal.dot(q);
+        let w = n / n.length_squared();
+        let area = n.length();
+        Quad { q, u, v, material, normal, d, w, area }
+    }
+
+    /// Distance along the ray to the quad and the coordinates along its edges there
+    fn intersect(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+        let denom = self.normal.dot(r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(r.origin)) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let planar = r.at(t) - self.q;
+        let alpha = self.w.dot(planar.cross(&self.v));
+        let beta = self.w.dot(self.u.cross(&planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some((t, alpha, beta))
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self,
+           r: &Ray,
+           t_min: f32,
+           t_max: f32)
+           -> Option<HitRecord>
+    {
+        let (t, alpha, beta) = self.intersect(r, t_min, t_max)?;
+        let mut result = HitRecord::new(r.at(t), self.normal, t, self.material.clone());
+        result.set_face_normal(r, &self.normal);
+        result.u = alpha;
+        result.v = beta;
+        result.dpdu = self.u;
+        result.dpdv = self.v;
+        if result.is_cut_out() {
+            return None;
+        }
+        Some(result)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Uniformly over the area of the quad
+    fn sample_direction(&self, origin: &Point3) -> Vec3 {
+        self.q + self.u * random::<f32>() + self.v * random::<f32>() - *origin
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f32 {
+        let direction = direction.unit_vector();
+        match self.intersect(&Ray::new(*origin, direction), 0.0, f32::INFINITY) {
+            Some((distance, _, _)) => {
+                let cosine = direction.dot(self.normal).abs();
+                if cosine <= 0.0 { 0.0 } else { distance * distance / (cosine * self.area) }
+            }
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{Camera, Color, Lambertian, Point3, Vec3};
+    use crate::hittable::Hittable;
+    use crate::quad::Quad;
+
+    #[test]
+    fn differentials_match_pixel_footprint() {
+        let camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0),
+                                 Vec3::new(0.0, 1.0, 0.0), 90.0, 1.0, 0.0, 1.0);
+        // The viewport spans 4 units at the quad, as does the quad itself
+        let quad = Quad::new(Point3::new(-2.0, -2.0, -2.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0),
+                             Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+
+        let ray = camera.get_ray_differential(0.5, 0.5, 0.1, 0.1);
+        let mut rec = quad.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        rec.compute_differentials(&ray);
+        assert!((rec.dudx - 0.1).abs() < 1e-4);
+        assert!((rec.dvdy - 0.1).abs() < 1e-4);
+        assert!(rec.dvdx.abs() < 1e-4 && rec.dudy.abs() < 1e-4);
+    }
+
+    #[test]
+    fn area_sampling_density() {
+        let quad = Quad::new(Point3::new(-1.0, 0.0, -1.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0),
+                             Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        let origin = Point3::new(0.0, 3.0, 0.0);
+        assert!((quad.pdf_value(&origin, &Vec3::new(0.0, -1.0, 0.0)) - 9.0 / 4.0).abs() < 1e-4);
+        assert_eq!(quad.pdf_value(&origin, &Vec3::new(1.0, 0.0, 0.0)), 0.0);
+
+        let direction = quad.sample_direction(&origin);
+        assert!(quad.pdf_value(&origin, &direction) > 0.0);
+    }
+}
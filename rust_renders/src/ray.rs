@@ -1,47 +1,36 @@
-use crate::{Color, Point3, Vec3};
-use crate::hittable::Hittable;
+use crate::{Point3, Vec3};
+use crate::medium::MediumStack;
+
+/// Rays through the neighbouring pixels, tracking the footprint of a pixel for texture filtering
+#[derive(Debug, Clone, Copy)]
+pub struct RayDifferential {
+    pub rx_origin: Point3,
+    pub rx_direction: Vec3,
+    pub ry_origin: Point3,
+    pub ry_direction: Vec3,
+}
 
 #[derive(Debug, Clone)]
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
+    /// Media the ray travels through
+    pub media: MediumStack,
+    /// Only known for camera rays and their specular continuations
+    pub differential: Option<RayDifferential>,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+        Ray { origin, direction, media: MediumStack::new(), differential: None }
     }
 
-    pub fn at(&self, t: f32) -> Point3 {
-        self.origin + self.direction * t
+    /// Continues the path with a new ray travelling through the same media
+    pub fn spawn(&self, origin: Point3, direction: Vec3) -> Ray {
+        Ray { origin, direction, media: self.media.clone(), differential: None }
     }
 
-    pub fn ray_color<T: Hittable>(&self,
-                                  world: &T,
-                                  color_scale: f32,
-                                  depth: u32)
-                                  -> Color
-    {
-        if depth == 0 {
-            return Color::new(0.0, 0.0, 0.0);
-        }
-
-        match world.hit(&self, 0.00001, f32::INFINITY) {
-            Some(rec) => {
-                let mut scattered: Ray = Ray::new(Point3::zero(), Vec3::zero());
-                let mut attenuation: Color = Color::new(0.0, 0.0, 0.0);
-
-                if rec.material.scatter(self, &rec, &mut attenuation, &mut scattered) {
-                    return scattered.ray_color(world, color_scale, depth - 1) * attenuation;
-                }
-                attenuation
-            }
-            None => {
-                let unit_direction = self.direction.unit_vector();
-                let t = 0.5 * (unit_direction[1] + 1.0);
-
-                Color::as_color(Vec3::new(1.0, 1.0, 1.0) * (1.0 - t) + Vec3::new(0.5, 0.7, 1.0) * t)
-            }
-        }
+    pub fn at(&self, t: f32) -> Point3 {
+        self.origin + self.direction * t
     }
 }
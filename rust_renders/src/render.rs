@@ -9,12 +9,10 @@ pub fn render_fn(config: Arc<ImageConfig>,
                  camera: Arc<Camera>,
                  world: Arc<HittableArray>)
                  -> Vec<Color> {
-    let color_scale_factor = 1.0 / config.samples_per_pixel as f32;
     let pool = ThreadPool::with_name("raytracer worker".to_string(),
                                      num_cpus::get());
 
-    let mut result = Vec::new();
-    let _ = (0..(config.height * config.width)).for_each(|_x| { result.push(Color::new(0., 0., 0.)) });
+    let result = vec![Color::new(0., 0., 0.); (config.height * config.width) as usize];
     let result = Arc::new(Mutex::new(result));
     // let parts = (0..config.height).ch
     for j in 0..config.height {
@@ -27,6 +25,11 @@ pub fn render_fn(config: Arc<ImageConfig>,
             let world: &HittableArray = world.borrow();
             let camera: &Camera = camera.borrow();
 
+            // Footprint of a single sample shrinks as more of them cover the pixel
+            let footprint = (1.0 / (config.samples_per_pixel as f32).sqrt()).max(0.125);
+            let du = footprint / ((config.width - 1) as f32);
+            let dv = footprint / ((config.height - 1) as f32);
+
             let mut temp_result = Vec::new();
             for i in 0..config.width {
                 let mut color = Color::new(0.0, 0.0, 0.0);
@@ -34,9 +37,8 @@ pub fn render_fn(config: Arc<ImageConfig>,
                     let u = ((i as f32) + rand::random::<f32>()) / ((config.width - 1) as f32);
                     let v = ((j as f32) + rand::random::<f32>()) / ((config.height - 1) as f32);
 
-                    let r = camera.get_ray(u, v);
-                    let borrowed_world: &HittableArray = world.borrow();
-                    let new_color = r.ray_color(borrowed_world, color_scale_factor, config.depth);
+                    let r = camera.get_ray_differential(u, v, du, dv);
+                    let new_color = config.integrator.radiance(&r, world);
                     color += new_color;
                 }
 
@@ -46,7 +48,7 @@ pub fn render_fn(config: Arc<ImageConfig>,
             let mut result_lock = result.lock().unwrap();
             let result: &mut Vec<Color> = result_lock.borrow_mut();
             for i in 0..config.width {
-                result[(j * config.width + i) as usize] = temp_result[i as usize].clone();
+                result[(j * config.width + i) as usize] = temp_result[i as usize];
             }
         });
     }
@@ -59,16 +61,22 @@ pub fn render_fn(config: Arc<ImageConfig>,
 }
 
 
+#[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
-    use crate::{Camera, Color, Glass, HittableArray, ImageConfig, Lambertian, Light, Metal, Point3, render_fn, Sphere, Vec3};
+    use crate::{Camera, Color, HittableArray, ImageConfig, Lambertian, Light, Metal, Point3, render_fn, Sphere, Vec3};
+    use crate::material::Glass;
 
     #[test]
     fn render_sample() {
-        let mut config = Arc::new(ImageConfig::default_config());
-        // config.height = 480;
-        // config.width = 640;
+        // Small enough for debug builds, the default config takes minutes there
+        let mut config = ImageConfig::default_config();
+        config.width = 64;
+        config.height = 36;
+        config.samples_per_pixel = 4;
+        config.color_scale = 1.0 / config.samples_per_pixel as f32;
+        let config = Arc::new(config);
 
         let look_from = Point3::new(-2.0, 2.0, 1.0);
         let look_at = Point3::new(0.0, 0.0, -1.0);
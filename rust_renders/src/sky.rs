@@ -0,0 +1,207 @@
+use std::f32::consts::PI;
+use rand::random;
+use crate::{Color, Vec3};
+use crate::onb::Onb;
+use crate::spectrum::xyz_to_rgb;
+
+/// Angular radius of the sun disk in radians
+const SUN_RADIUS: f32 = 0.004_65;
+/// Illuminance of sunlight outside of the atmosphere in lux
+const SUN_ILLUMINANCE: f32 = 128_000.0;
+/// Wavelengths in micrometers standing in for the red, green and blue channels
+const CHANNEL_WAVELENGTHS: [f32; 3] = [0.65, 0.57, 0.475];
+
+/// Coefficients of the Perez sky distribution function
+#[derive(Debug, Clone, Copy)]
+struct Perez {
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    e: f32,
+}
+
+impl Perez {
+    /// Relative value at the view angle `theta` from the zenith and angle `gamma` from the sun
+    fn f(&self, cos_theta: f32, gamma: f32) -> f32 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a * (self.b / cos_theta.max(1e-3)).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma)
+    }
+}
+
+/// Clear sky of Preetham, Shirley and Smits 1999 with the sun disk in it. Radiance is in cd/m^2,
+/// matching the other lights, below the horizon is black
+#[derive(Debug, Clone)]
+pub struct PhysicalSky {
+    /// Unit vector towards the sun
+    sun: Vec3,
+    /// Angle of the sun from the zenith, at most at the horizon
+    sun_theta: f32,
+    perez: [Perez; 3],
+    /// Luminance and chromaticity at the zenith
+    zenith: [f32; 3],
+    /// Radiance of the sun disk after passing the atmosphere
+    sun_radiance: Color,
+    intensity: f32,
+}
+
+impl PhysicalSky {
+    /// Sky lit by the sun in direction `sun`. Turbidity describes the haze, 2 is a very clear sky
+    /// and 10 a hazy one
+    pub fn new(sun: Vec3, turbidity: f32) -> PhysicalSky {
+        let sun = sun.unit_vector();
+        let t = turbidity.clamp(1.7, 10.0);
+        // Below the horizon the model breaks down, the sky stays as it is at sunset
+        let sun_theta = sun[1].clamp(0.0, 1.0).acos();
+
+        let perez = [
+            Perez { a: 0.1787 * t - 1.4630, b: -0.3554 * t + 0.4275, c: -0.0227 * t + 5.3251,
+                    d: 0.1206 * t - 2.5771, e: -0.0670 * t + 0.3703 },
+            Perez { a: -0.0193 * t - 0.2592, b: -0.0665 * t + 0.0008, c: -0.0004 * t + 0.2125,
+                    d: -0.0641 * t - 0.8989, e: -0.0033 * t + 0.0452 },
+            Perez { a: -0.0167 * t - 0.2608, b: -0.0950 * t + 0.0092, c: -0.0079 * t + 0.2102,
+                    d: -0.0441 * t - 1.6537, e: -0.0109 * t + 0.0529 },
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * sun_theta);
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192) * 1000.0;
+        let cubic = |c: [f32; 4]| ((c[0] * sun_theta + c[1]) * sun_theta + c[2]) * sun_theta + c[3];
+        let x = t * t * cubic([0.00166, -0.00375, 0.00209, 0.0])
+            + t * cubic([-0.02903, 0.06377, -0.03202, 0.00394])
+            + cubic([0.11693, -0.21196, 0.06052, 0.25886]);
+        let y = t * t * cubic([0.00275, -0.00610, 0.00317, 0.0])
+            + t * cubic([-0.04214, 0.08970, -0.04153, 0.00516])
+            + cubic([0.15346, -0.26756, 0.06670, 0.26688]);
+
+        // Rayleigh and aerosol extinction along the path of sunlight through the air mass
+        let air_mass = 1.0 / (sun_theta.cos() + 0.15 * (93.885 - sun_theta.to_degrees()).powf(-1.253));
+        let beta = 0.04608 * t - 0.04586;
+        let transmittance = CHANNEL_WAVELENGTHS.map(|lambda| {
+            (-air_mass * (0.008735 * lambda.powf(-4.08) + beta * lambda.powf(-1.3))).exp()
+        });
+        let solid_angle = 2.0 * PI * (1.0 - SUN_RADIUS.cos());
+        let sun_radiance = Color::new(transmittance[0], transmittance[1], transmittance[2])
+            * (SUN_ILLUMINANCE / solid_angle);
+
+        PhysicalSky { sun, sun_theta, perez, zenith: [luminance.max(0.0), x, y], sun_radiance, intensity: 1.0 }
+    }
+
+    /// Sky at a time of day, see `sun_direction`
+    pub fn at_time(latitude: f32, day_of_year: u32, solar_time: f32, turbidity: f32) -> PhysicalSky {
+        PhysicalSky::new(PhysicalSky::sun_direction(latitude, day_of_year, solar_time), turbidity)
+    }
+
+    /// Direction towards the sun at `latitude` in degrees, on a day of the year starting at 1 and at
+    /// a solar time in hours, where the sun is highest at 12. North is -z and east is +x
+    pub fn sun_direction(latitude: f32, day_of_year: u32, solar_time: f32) -> Vec3 {
+        let declination = 0.4093 * (2.0 * PI * (day_of_year as f32 - 81.0) / 368.0).sin();
+        let hour_angle = PI * (solar_time - 12.0) / 12.0;
+        let (sin_lat, cos_lat) = latitude.to_radians().sin_cos();
+        let (sin_dec, cos_dec) = declination.sin_cos();
+        let up = sin_lat * sin_dec + cos_lat * cos_dec * hour_angle.cos();
+        let east = -cos_dec * hour_angle.sin();
+        let north = cos_lat * sin_dec - sin_lat * cos_dec * hour_angle.cos();
+        Vec3::new(east, up, -north)
+    }
+
+    /// Scales the sky and the sun, to bring daylight into the range of the image
+    pub fn with_intensity(mut self, intensity: f32) -> PhysicalSky {
+        self.intensity = intensity;
+        self
+    }
+
+    /// One minus the cosine of the sun radius, without the cancellation of computing it directly
+    fn sun_one_minus_cos() -> f32 {
+        2.0 * (0.5 * SUN_RADIUS).sin().powi(2)
+    }
+
+    fn in_sun(&self, direction: &Vec3) -> bool {
+        // Tolerance keeps sampled directions at the very rim inside of the disk
+        direction.dot(self.sun) >= 1.0 - 1.001 * PhysicalSky::sun_one_minus_cos()
+    }
+
+    pub fn color(&self, direction: &Vec3) -> Color {
+        let d = direction.unit_vector();
+        if d[1] <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let gamma = d.dot(self.sun).clamp(-1.0, 1.0).acos();
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * self.perez[i].f(d[1], gamma) / self.perez[i].f(1.0, self.sun_theta)
+        });
+        let sky = if y > 0.0 {
+            let rgb = xyz_to_rgb([x / y * luminance, luminance, (1.0 - x - y) / y * luminance]);
+            Color::new(rgb[0].max(0.0), rgb[1].max(0.0), rgb[2].max(0.0))
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+
+        let sun = if self.in_sun(&d) { self.sun_radiance } else { Color::new(0.0, 0.0, 0.0) };
+        (sky + sun) * self.intensity
+    }
+
+    /// Chance of sampling the sun disk instead of the sky dome
+    fn sun_probability(&self) -> f32 {
+        if self.sun[1] > 0.0 { 0.5 } else { 0.0 }
+    }
+
+    /// Random direction towards either the sun disk or the sky above the horizon
+    pub fn sample_direction(&self) -> Vec3 {
+        if random::<f32>() < self.sun_probability() {
+            let z = 1.0 - random::<f32>() * PhysicalSky::sun_one_minus_cos();
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            let phi = 2.0 * PI * random::<f32>();
+            Onb::from_w(&self.sun).local(&Vec3::new(r * phi.cos(), r * phi.sin(), z))
+        } else {
+            let y = random::<f32>();
+            let r = (1.0 - y * y).max(0.0).sqrt();
+            let phi = 2.0 * PI * random::<f32>();
+            Vec3::new(r * phi.cos(), y, r * phi.sin())
+        }
+    }
+
+    /// Solid angle density of `sample_direction` producing `direction`
+    pub fn pdf_value(&self, direction: &Vec3) -> f32 {
+        let d = direction.unit_vector();
+        let sun_probability = self.sun_probability();
+        let mut pdf = 0.0;
+        if self.in_sun(&d) {
+            pdf += sun_probability / (2.0 * PI * PhysicalSky::sun_one_minus_cos());
+        }
+        if d[1] > 0.0 {
+            pdf += (1.0 - sun_probability) / (2.0 * PI);
+        }
+        pdf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Vec3;
+    use crate::sky::PhysicalSky;
+
+    #[test]
+    fn sky_follows_the_sun() {
+        // Equinox noon at the equator puts the sun overhead
+        let sun = PhysicalSky::sun_direction(0.0, 81, 12.0);
+        assert!((sun[1] - 1.0).abs() < 1e-4);
+        // Mornings in the northern summer have the sun in the north east
+        let morning = PhysicalSky::sun_direction(50.0, 172, 7.0);
+        assert!(morning[0] > 0.0 && morning[1] > 0.0 && morning[2] < 0.0);
+
+        let sky = PhysicalSky::new(Vec3::new(1.0, 0.5, 0.0), 3.0);
+        let zenith = sky.color(&Vec3::new(0.0, 1.0, 0.0)).luminance();
+        assert!((zenith / sky.zenith[0] - 1.0).abs() < 0.05);
+        // Brighter around the sun than opposite of it, and the disk outshines everything
+        assert!(sky.color(&Vec3::new(1.0, 0.3, 0.2)).luminance() > sky.color(&Vec3::new(-1.0, 0.3, 0.2)).luminance());
+        assert!(sky.color(&Vec3::new(1.0, 0.5, 0.0)).luminance() > 1e8);
+        assert_eq!(sky.color(&Vec3::new(0.0, -1.0, 0.0)).luminance(), 0.0);
+
+        for _ in 0..100 {
+            let direction = sky.sample_direction();
+            assert!(sky.pdf_value(&direction) > 0.0);
+        }
+    }
+}
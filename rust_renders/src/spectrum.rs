@@ -0,0 +1,97 @@
+use std::sync::OnceLock;
+use crate::Color;
+
+/// Wavelengths in nanometers used to integrate spectra, visible range with 10nm step
+pub fn wavelengths() -> impl Iterator<Item = f32> {
+    (0..31).map(|i| 400.0 + 10.0 * i as f32)
+}
+
+fn piecewise_gaussian(x: f32, mu: f32, sigma_low: f32, sigma_high: f32) -> f32 {
+    let t = (x - mu) / if x < mu { sigma_low } else { sigma_high };
+    (-0.5 * t * t).exp()
+}
+
+/// CIE 1931 color matching functions, multi-lobe fit of Wyman, Sloan and Shirley 2013
+pub fn cie_xyz(lambda: f32) -> [f32; 3] {
+    [
+        1.056 * piecewise_gaussian(lambda, 599.8, 37.9, 31.0)
+            + 0.362 * piecewise_gaussian(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * piecewise_gaussian(lambda, 501.1, 20.4, 26.2),
+        0.821 * piecewise_gaussian(lambda, 568.8, 46.9, 40.5)
+            + 0.286 * piecewise_gaussian(lambda, 530.9, 16.3, 31.1),
+        1.217 * piecewise_gaussian(lambda, 437.0, 11.8, 36.0)
+            + 0.681 * piecewise_gaussian(lambda, 459.0, 26.0, 13.8),
+    ]
+}
+
+/// Converts CIE XYZ into linear sRGB, the working color space of the renderer
+pub fn xyz_to_rgb(xyz: [f32; 3]) -> Color {
+    Color::new(3.2406 * xyz[0] - 1.5372 * xyz[1] - 0.4986 * xyz[2],
+               -0.9689 * xyz[0] + 1.8758 * xyz[1] + 0.0415 * xyz[2],
+               0.0557 * xyz[0] - 0.2040 * xyz[1] + 1.0570 * xyz[2])
+}
+
+/// Integrates a spectrum sampled at `wavelengths` into linear sRGB
+pub fn spectrum_to_rgb<F: Fn(f32) -> f32>(spectrum: F) -> Color {
+    let mut xyz = [0.0f32; 3];
+    for lambda in wavelengths() {
+        let value = spectrum(lambda);
+        let cmf = cie_xyz(lambda);
+        for (acc, c) in xyz.iter_mut().zip(cmf.iter()) {
+            *acc += value * c;
+        }
+    }
+    xyz_to_rgb(xyz)
+}
+
+/// Reflectance spectrum as seen in linear sRGB, a constant spectrum gives a gray color of the same value.
+/// Colors outside of sRGB gamut are clamped
+pub fn reflectance_to_rgb<F: Fn(f32) -> f32>(reflectance: F) -> Color {
+    // Same for every call, and thin films convert a spectrum per hit
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    let white = WHITE.get_or_init(|| spectrum_to_rgb(|_| 1.0));
+    let color = spectrum_to_rgb(reflectance);
+    Color::new((color[0] / white[0]).clamp(0.0, 1.0),
+               (color[1] / white[1]).clamp(0.0, 1.0),
+               (color[2] / white[2]).clamp(0.0, 1.0))
+}
+
+/// Planck's law, spectral radiance of a black body in W/(sr m^2 nm) at wavelength `lambda` in nanometers
+pub fn planck(lambda: f32, temperature: f32) -> f32 {
+    const H: f64 = 6.626_070_15e-34;
+    const C: f64 = 2.997_924_58e8;
+    const K_B: f64 = 1.380_649e-23;
+
+    let lambda = lambda as f64 * 1e-9;
+    let radiance = 2.0 * H * C * C / (lambda.powi(5) * ((H * C / (lambda * K_B * temperature as f64)).exp() - 1.0));
+    (radiance * 1e-9) as f32
+}
+
+/// Color of a black body at `temperature` Kelvin in linear sRGB, normalized to unit luminance
+pub fn blackbody(temperature: f32) -> Color {
+    let color = spectrum_to_rgb(|lambda| planck(lambda, temperature));
+    let luminance = color.luminance();
+    Color::new((color[0] / luminance).max(0.0),
+               (color[1] / luminance).max(0.0),
+               (color[2] / luminance).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spectrum::blackbody;
+
+    #[test]
+    fn blackbody_at_6500k_is_near_white() {
+        // D65 is the white point of sRGB and lies close to the Planckian locus
+        let daylight = blackbody(6500.0);
+        assert!((daylight.luminance() - 1.0).abs() < 1e-4);
+        for i in 0..3 {
+            assert!((daylight[i] - 1.0).abs() < 0.06, "{:?}", daylight);
+        }
+
+        let candle = blackbody(1900.0);
+        let sky = blackbody(12000.0);
+        assert!(candle[0] > candle[1] && candle[1] > candle[2]);
+        assert!(sky[2] > sky[1] && sky[1] > sky[0]);
+    }
+}
@@ -1,7 +1,10 @@
+use std::f32::consts::PI;
 use std::sync::Arc;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
-use crate::Point3;
+use rand::random;
+use crate::{Point3, Vec3};
+use crate::onb::Onb;
 use crate::ray::Ray;
 
 #[derive(Debug, Clone)]
@@ -19,6 +22,38 @@ impl Sphere {
     {
         Sphere { center, radius, material }
     }
+
+    /// Cosine of the half angle of the cone the sphere fills as seen from `origin`,
+    /// with one minus it computed without cancellation for small cones. `None` from inside of the sphere
+    fn visible_cone(&self, origin: &Point3) -> Option<(f32, f32)> {
+        let sin2_theta_max = self.radius * self.radius / (self.center - *origin).length_squared();
+        if sin2_theta_max >= 1.0 {
+            return None;
+        }
+        let cos_theta_max = (1.0 - sin2_theta_max).sqrt();
+        Some((cos_theta_max, sin2_theta_max / (1.0 + cos_theta_max)))
+    }
+
+    fn hit_record(&self, r: &Ray, t: f32) -> HitRecord {
+        let p = r.at(t);
+        let outward_normal = (p - self.center) / self.radius;
+        let mut result = HitRecord::new(p, outward_normal, t, self.material.clone());
+        result.set_face_normal(r, &outward_normal);
+
+        let theta = (-outward_normal[1]).clamp(-1.0, 1.0).acos();
+        let phi = (-outward_normal[2]).atan2(outward_normal[0]) + PI;
+        result.u = phi / (2.0 * PI);
+        result.v = theta / PI;
+
+        let (x, y, z) = (outward_normal[0], outward_normal[1], outward_normal[2]);
+        let sin_theta = (1.0 - y * y).max(1e-6).sqrt();
+        result.dpdu = Vec3::new(z, 0.0, -x) * (2.0 * PI * self.radius);
+        result.dpdv = Vec3::new(-x * y / sin_theta, sin_theta, -y * z / sin_theta) * (PI * self.radius);
+        let side = if result.front_face { 1.0 } else { -1.0 };
+        result.dndu = result.dpdu * (side / self.radius);
+        result.dndv = result.dpdv * (side / self.radius);
+        result
+    }
 }
 
 impl Hittable for Sphere {
@@ -37,26 +72,44 @@ impl Hittable for Sphere {
         if descriminant > 0.0 {
             let root = descriminant.sqrt();
 
-            let temp = (-half_b - root) / a;
-            if temp < t_max && temp > t_min {
-                let p = r.at(temp);
-                let outward_normal = (p - self.center) / self.radius;
-                let mut result = HitRecord::new(p, (p - self.center) / self.radius, temp, self.material.clone());
-                result.set_face_normal(r, &outward_normal);
-                return Some(result);
+            for temp in [(-half_b - root) / a, (-half_b + root) / a] {
+                if temp < t_max && temp > t_min {
+                    let result = self.hit_record(r, temp);
+                    if !result.is_cut_out() {
+                        return Some(result);
+                    }
+                }
             }
+        }
 
-            let temp = (-half_b + root) / a;
-            if temp < t_max && temp > t_min {
-                let p = r.at(temp);
-                let outward_normal = (p - self.center) / self.radius;
-                let mut result = HitRecord::new(p, (p - self.center) / self.radius, temp, self.material.clone());
-                result.set_face_normal(r, &outward_normal);
-                return Some(result);
+        None
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Uniformly within the cone of directions hitting the sphere
+    fn sample_direction(&self, origin: &Point3) -> Vec3 {
+        let (_, one_minus_cos) = match self.visible_cone(origin) {
+            Some(cone) => cone,
+            None => return Vec3::rand_unit_sphere(),
+        };
+        let z = 1.0 - random::<f32>() * one_minus_cos;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * random::<f32>();
+        Onb::from_w(&(self.center - *origin).unit_vector()).local(&Vec3::new(r * phi.cos(), r * phi.sin(), z))
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f32 {
+        match self.visible_cone(origin) {
+            Some((cos_theta_max, one_minus_cos)) => {
+                let cosine = direction.unit_vector().dot((self.center - *origin).unit_vector());
+                // Tolerance keeps sampled directions at the very rim inside of the cone
+                if cosine < cos_theta_max - 1e-3 * one_minus_cos { 0.0 } else { 1.0 / (2.0 * PI * one_minus_cos) }
             }
+            None => 1.0 / (4.0 * PI),
         }
-
-        return None;
     }
 }
 
@@ -0,0 +1,85 @@
+use crate::Color;
+use crate::hittable::HitRecord;
+use crate::material::{Glass, Material, ScatterRecord};
+use crate::medium::Medium;
+use crate::ray::Ray;
+
+/// Translucent material scattering light inside of the object, like skin, wax or marble.
+/// Light enters through a smooth dielectric surface and random walks through the interior
+#[derive(Debug, Clone)]
+pub struct Subsurface {
+    surface: Glass,
+}
+
+impl Subsurface {
+    /// `albedo` is the color the surface appears under diffuse lighting,
+    /// `mean_free_path` is the average distance light travels in the medium between scattering events
+    pub fn new(albedo: Color, mean_free_path: Color, ref_idx: f32) -> Subsurface {
+        let mut absorption = [0.0f32; 3];
+        let mut scattering = [0.0f32; 3];
+        for c in 0..3 {
+            let extinction = 1.0 / mean_free_path[c].max(1e-6);
+            let single_scattering = Subsurface::single_scattering_albedo(albedo[c]);
+            scattering[c] = extinction * single_scattering;
+            absorption[c] = extinction - scattering[c];
+        }
+
+        let medium = Medium::participating(ref_idx,
+                                           Color::new(absorption[0], absorption[1], absorption[2]),
+                                           Color::new(scattering[0], scattering[1], scattering[2]),
+                                           0);
+        Subsurface { surface: Glass::with_medium(medium) }
+    }
+
+    /// Inverts the multiple scattering albedo of a semi-infinite medium (van de Hulst)
+    fn single_scattering_albedo(albedo: f32) -> f32 {
+        let a = albedo.clamp(0.0, 0.999);
+        let s = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+        1.0 - s * s
+    }
+}
+
+impl Material for Subsurface {
+    fn sample(&self, r_in: &Ray, hit_rec: &HitRecord) -> Option<ScatterRecord> {
+        self.surface.sample(r_in, hit_rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::{Color, HittableArray, Point3, Sphere, Vec3};
+    use crate::background::Background;
+    use crate::integrator::{Integrator, PathTracer};
+    use crate::ray::Ray;
+    use crate::subsurface::Subsurface;
+
+    #[test]
+    fn single_scattering_albedo_is_monotonic() {
+        assert!(Subsurface::single_scattering_albedo(0.0).abs() < 1e-3);
+        let mut previous = 0.0;
+        for i in 1..=10 {
+            let single = Subsurface::single_scattering_albedo(i as f32 / 10.0);
+            assert!(single > previous && single <= 1.0);
+            previous = single;
+        }
+    }
+
+    #[test]
+    fn translucent_sphere_does_not_create_energy() {
+        let radiance = |albedo: f32| {
+            let mut world = HittableArray::new();
+            let material = Subsurface::new(Color::new(albedo, albedo, albedo), Color::new(0.2, 0.2, 0.2), 1.3);
+            world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Arc::new(material))));
+            world.set_background(Background::Constant(Color::new(1.0, 1.0, 1.0)));
+            let ray = Ray::new(Point3::new(0.0, 0.0, 3.0), Vec3::new(0.1, 0.2, -1.0));
+            let tracer = PathTracer::new(64, 64);
+            (0..2000).map(|_| tracer.radiance(&ray, &world)[0]).sum::<f32>() / 2000.0
+        };
+        let white = radiance(1.0);
+        let grey = radiance(0.5);
+        // The interior walk is cut short at times, so even white loses a little
+        assert!(white > 0.8 && white <= 1.01, "{}", white);
+        assert!(grey < white, "{} {}", grey, white);
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use crate::Color;
+use crate::hittable::HitRecord;
+use crate::image::{Image, MipMap};
+
+pub trait Texture: Debug + Send + Sync {
+    fn value(&self, hit_rec: &HitRecord) -> Color;
+}
+
+#[derive(Debug, Clone)]
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _hit_rec: &HitRecord) -> Color {
+        self.color
+    }
+}
+
+/// 3D checker pattern alternating two textures
+#[derive(Debug, Clone)]
+pub struct Checker {
+    odd: Arc<dyn Texture>,
+    even: Arc<dyn Texture>,
+    /// Size of a single cell
+    scale: f32,
+}
+
+impl Checker {
+    pub fn new(odd: Arc<dyn Texture>, even: Arc<dyn Texture>, scale: f32) -> Checker {
+        Checker { odd, even, scale }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, hit_rec: &HitRecord) -> Color {
+        let p = hit_rec.p / self.scale;
+        let cell = p[0].floor() as i64 + p[1].floor() as i64 + p[2].floor() as i64;
+        if cell % 2 == 0 { self.even.value(hit_rec) } else { self.odd.value(hit_rec) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Full resolution only, aliases in the distance
+    Bilinear,
+    /// Level chosen by the larger extent of the pixel footprint, blurs surfaces seen at grazing angles
+    Trilinear,
+    /// Multiple samples along elongated footprints, with the given limit on their count
+    Anisotropic(f32),
+}
+
+/// Image mapped over surface coordinates, filtered over the pixel footprint given by ray differentials
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    mipmap: Arc<MipMap>,
+    filter: Filter,
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<Image>) -> ImageTexture {
+        ImageTexture::from_mipmap(Arc::new(MipMap::new((*image).clone())))
+    }
+
+    /// Shares a prebuilt pyramid between textures
+    pub fn from_mipmap(mipmap: Arc<MipMap>) -> ImageTexture {
+        ImageTexture { mipmap, filter: Filter::Trilinear }
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> ImageTexture {
+        self.filter = filter;
+        self
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, hit_rec: &HitRecord) -> Color {
+        let (u, v) = (hit_rec.u, hit_rec.v);
+        let dx = (hit_rec.dudx, hit_rec.dvdx);
+        let dy = (hit_rec.dudy, hit_rec.dvdy);
+        match self.filter {
+            Filter::Bilinear => self.mipmap.level(0).sample(u, v),
+            Filter::Trilinear => {
+                let width = dx.0.abs().max(dx.1.abs()).max(dy.0.abs()).max(dy.1.abs());
+                self.mipmap.trilinear(u, v, width)
+            }
+            Filter::Anisotropic(max_anisotropy) => self.mipmap.anisotropic(u, v, dx, dy, max_anisotropy),
+        }
+    }
+}
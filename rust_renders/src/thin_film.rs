@@ -0,0 +1,183 @@
+use std::f32::consts::PI;
+use std::ops::{Add, Div, Mul, Sub};
+use std::sync::Arc;
+use crate::Color;
+use crate::hittable::HitRecord;
+use crate::spectrum::reflectance_to_rgb;
+use crate::texture::Texture;
+
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    fn new(re: f32, im: f32) -> Complex {
+        Complex { re, im }
+    }
+
+    fn norm_sqr(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// Principal square root
+    fn sqrt(self) -> Complex {
+        let r = self.norm_sqr().sqrt();
+        let re = (0.5 * (r + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (r - self.re)).max(0.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    /// `exp(i * self)`
+    fn exp_i(self) -> Complex {
+        let m = (-self.im).exp();
+        Complex::new(m * self.re.cos(), m * self.re.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re * rhs.re - self.im * rhs.im, self.re * rhs.im + self.im * rhs.re)
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        let d = rhs.norm_sqr();
+        Complex::new((self.re * rhs.re + self.im * rhs.im) / d, (self.im * rhs.re - self.re * rhs.im) / d)
+    }
+}
+
+/// Cosine of the refracted angle in a medium with refraction index `n`, given `n * sin` is conserved
+fn refracted_cos(n_sin: f32, n: Complex) -> Complex {
+    let s = Complex::new(n_sin, 0.0) / n;
+    (Complex::new(1.0, 0.0) - s * s).sqrt()
+}
+
+/// Fresnel amplitude coefficients for s and p polarizations
+fn fresnel_amplitudes(n_i: Complex, cos_i: Complex, n_t: Complex, cos_t: Complex) -> (Complex, Complex) {
+    let s = (n_i * cos_i - n_t * cos_t) / (n_i * cos_i + n_t * cos_t);
+    let p = (n_t * cos_i - n_i * cos_t) / (n_t * cos_i + n_i * cos_t);
+    (s, p)
+}
+
+/// Thin transparent film coating a surface, like soap or oil, producing interference colors
+#[derive(Debug, Clone)]
+pub struct ThinFilm {
+    /// Film thickness in nanometers
+    thickness: f32,
+    /// Refraction index of the film
+    ref_idx: f32,
+    /// Optional thickness variation, luminance of the texture scales `thickness`
+    thickness_map: Option<Arc<dyn Texture>>,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f32, ref_idx: f32) -> ThinFilm {
+        ThinFilm { thickness, ref_idx, thickness_map: None }
+    }
+
+    pub fn textured(thickness: f32, ref_idx: f32, thickness_map: Arc<dyn Texture>) -> ThinFilm {
+        ThinFilm { thickness, ref_idx, thickness_map: Some(thickness_map) }
+    }
+
+    fn thickness_at(&self, hit_rec: &HitRecord) -> f32 {
+        match &self.thickness_map {
+            Some(map) => self.thickness * map.value(hit_rec).luminance(),
+            None => self.thickness,
+        }
+    }
+
+    /// Airy reflectance of the film between the incident medium and the substrate at a single wavelength
+    fn airy_reflectance(&self, thickness: f32, lambda: f32, cos_i: f32, n_i: f32, n_substrate: Complex) -> f32 {
+        let n_sin = n_i * (1.0 - cos_i * cos_i).max(0.0).sqrt();
+        let n_i = Complex::new(n_i, 0.0);
+        let n_film = Complex::new(self.ref_idx, 0.0);
+        let cos_i = Complex::new(cos_i, 0.0);
+        let cos_film = refracted_cos(n_sin, n_film);
+        let cos_substrate = refracted_cos(n_sin, n_substrate);
+
+        let (r12_s, r12_p) = fresnel_amplitudes(n_i, cos_i, n_film, cos_film);
+        let (r23_s, r23_p) = fresnel_amplitudes(n_film, cos_film, n_substrate, cos_substrate);
+
+        // Phase difference between the light reflected on the two interfaces of the film
+        let phase = (n_film * cos_film * Complex::new(4.0 * PI * thickness / lambda, 0.0)).exp_i();
+        let one = Complex::new(1.0, 0.0);
+        let r_s = (r12_s + r23_s * phase) / (one + r12_s * r23_s * phase);
+        let r_p = (r12_p + r23_p * phase) / (one + r12_p * r23_p * phase);
+        (0.5 * (r_s.norm_sqr() + r_p.norm_sqr())).min(1.0)
+    }
+
+    /// Reflectance of the film over a dielectric
+    pub fn dielectric_reflectance(&self, hit_rec: &HitRecord, cos_i: f32, n_i: f32, n_substrate: f32) -> Color {
+        let thickness = self.thickness_at(hit_rec);
+        let n_substrate = Complex::new(n_substrate, 0.0);
+        reflectance_to_rgb(|lambda| self.airy_reflectance(thickness, lambda, cos_i, n_i, n_substrate))
+    }
+
+    /// Reflectance of the film over a conductor with complex refraction index `eta + i * k` given per color channel
+    pub fn conductor_reflectance(&self,
+                                 hit_rec: &HitRecord,
+                                 cos_i: f32,
+                                 n_i: f32,
+                                 eta: &Color,
+                                 k: &Color)
+                                 -> Color
+    {
+        let thickness = self.thickness_at(hit_rec);
+        reflectance_to_rgb(|lambda| {
+            let n_substrate = Complex::new(channel_at(eta, lambda), channel_at(k, lambda));
+            self.airy_reflectance(thickness, lambda, cos_i, n_i, n_substrate)
+        })
+    }
+}
+
+/// Interpolates RGB channels over wavelength, taking them as samples at 650, 550 and 450nm
+fn channel_at(color: &Color, lambda: f32) -> f32 {
+    if lambda >= 650.0 {
+        color[0]
+    } else if lambda >= 550.0 {
+        let t = (lambda - 550.0) / 100.0;
+        color[1] * (1.0 - t) + color[0] * t
+    } else if lambda >= 450.0 {
+        let t = (lambda - 450.0) / 100.0;
+        color[2] * (1.0 - t) + color[1] * t
+    } else {
+        color[2]
+    }
+}
+
+/// Complex refraction index of a conductor from its reflectivity and edge tint (Gulbrandsen 2014)
+pub fn conductor_ior(reflectivity: &Color, edge_tint: &Color) -> (Color, Color) {
+    let mut eta = [0.0f32; 3];
+    let mut k = [0.0f32; 3];
+    for c in 0..3 {
+        let r = reflectivity[c].clamp(0.0, 0.99);
+        let g = edge_tint[c];
+        let sqrt_r = r.sqrt();
+        eta[c] = g * (1.0 - r) / (1.0 + r) + (1.0 - g) * (1.0 + sqrt_r) / (1.0 - sqrt_r);
+        k[c] = ((r * (eta[c] + 1.0).powi(2) - (eta[c] - 1.0).powi(2)) / (1.0 - r)).max(0.0).sqrt();
+    }
+    (Color::new(eta[0], eta[1], eta[2]), Color::new(k[0], k[1], k[2]))
+}
@@ -2,9 +2,9 @@ use std::ops::{Mul, Add, Div, MulAssign, AddAssign, DivAssign, Neg, Index, Sub,
 use std::fmt;
 use rand::{Rng, random};
 
-#[cfg(target_arch = "x86")]
+#[cfg(all(target_arch = "x86", feature = "enable_sse"))]
 use std::arch::x86::*;
-#[cfg(target_arch = "x86_64")]
+#[cfg(all(target_arch = "x86_64", feature = "enable_sse"))]
 use std::arch::x86_64::*;
 use crate::ImageConfig;
 
@@ -22,7 +22,7 @@ pub struct Vec3 {
     z: f32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub struct Color {
     imp: Vec3,
 }
@@ -35,6 +35,21 @@ impl Color {
     pub fn as_color(v: Vec3) -> Color {
         Color { imp: v }
     }
+
+    /// Per-channel exponent, used for Beer–Lambert transmittance
+    pub fn exp(&self) -> Color {
+        Color::new(self.imp[0].exp(), self.imp[1].exp(), self.imp[2].exp())
+    }
+
+    /// Relative luminance of linear sRGB color
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.imp[0] + 0.7152 * self.imp[1] + 0.0722 * self.imp[2]
+    }
+
+    /// Per-channel natural logarithm
+    pub fn ln(&self) -> Color {
+        Color::new(self.imp[0].ln(), self.imp[1].ln(), self.imp[2].ln())
+    }
 }
 
 impl Mul for Color {
@@ -53,6 +68,14 @@ impl Add for Color {
     }
 }
 
+impl Sub for Color {
+    type Output = Color;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Color { imp: self.imp - rhs.imp }
+    }
+}
+
 impl AddAssign for Color {
     fn add_assign(&mut self, rhs: Self) {
         self.imp += rhs.imp;
@@ -314,6 +337,15 @@ impl Index<usize> for Vec3 {
     }
 }
 
+impl Index<usize> for Color {
+    type Output = f32;
+
+    #[track_caller]
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.imp[index]
+    }
+}
+
 impl fmt::Display for Vec3 {
     #[cfg(feature = "enable_sse")]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -427,7 +459,7 @@ impl Vec3 {
     }
 
     #[cfg(not(feature = "enable_sse"))]
-    pub fn length_squared(mut self) -> f32 {
+    pub fn length_squared(self) -> f32 {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
 
@@ -456,7 +488,7 @@ impl Vec3 {
     }
 
     #[cfg(not(feature = "enable_sse"))]
-    pub fn dot(self, mut rhs: Vec3) -> f32 {
+    pub fn dot(self, rhs: Vec3) -> f32 {
         self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
     }
 
@@ -499,6 +531,15 @@ impl Vec3 {
         Vec3::new(r * a.cos(), r * a.sin(), z)
     }
 
+    /// Cosine-weighted direction on the hemisphere around z axis
+    pub fn rand_cosine_direction() -> Vec3 {
+        let r1 = random::<f32>();
+        let r2 = random::<f32>();
+        let phi = 2.0 * std::f32::consts::PI * r1;
+        let r = r2.sqrt();
+        Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - r2).sqrt())
+    }
+
     /// Reflect vector according to the normal of the surface
     pub fn reflect(v: &Vec3, normal: &Vec3) -> Vec3 {
         *v - (*normal * (v.dot(*normal) * 2.0))